@@ -0,0 +1,93 @@
+//! Switching the dolphin between its Aseprite tags.
+
+use std::collections::HashMap;
+
+use benimator::{Play, SpriteSheetAnimation};
+use bevy::prelude::*;
+
+use crate::{Faith, LoadingState};
+
+pub const SWIM: &str = "swim";
+pub const BONK: &str = "bonk";
+pub const EAT: &str = "eat";
+pub const DIVE: &str = "dive";
+pub const BREACH: &str = "breach";
+pub const BACKFLIP: &str = "backflip";
+pub const TAIL_WALK: &str = "tailwalk";
+
+/// Every tag the dolphin can play, and whether it loops.
+/// One-shot tags go back to [`SWIM`] once they finish.
+pub const FAITH_TAGS: [(&str, bool); 7] = [
+    (SWIM, true),
+    (BONK, false),
+    (EAT, false),
+    (DIVE, true),
+    (BREACH, false),
+    (BACKFLIP, false),
+    (TAIL_WALK, true),
+];
+
+#[derive(Debug, Clone)]
+pub struct TagAnimation {
+    pub animation: Handle<SpriteSheetAnimation>,
+    pub atlas: Handle<TextureAtlas>,
+    pub looping: bool,
+}
+
+/// Animations for each of [`FAITH_TAGS`] that `faith.ase` actually has.
+#[derive(Debug, Default)]
+pub struct FaithAnimations(pub HashMap<&'static str, TagAnimation>);
+
+impl FaithAnimations {
+    pub fn get(&self, tag: &str) -> Option<&TagAnimation> {
+        self.0.get(tag)
+    }
+
+    /// Switches `entity` over to the animation for `tag`,
+    /// returning `false` if `faith.ase` doesn't have that tag.
+    pub fn play(&self, commands: &mut Commands, entity: Entity, tag: &str) -> bool {
+        if let Some(tag) = self.get(tag) {
+            let mut entity = commands.entity(entity);
+            entity
+                .insert(tag.animation.clone())
+                .insert(tag.atlas.clone())
+                .insert(Play);
+            if tag.looping {
+                entity.remove::<OneShot>();
+            } else {
+                entity.insert(OneShot);
+            }
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Marks an entity that's in the middle of a one-shot animation.
+#[derive(Component)]
+struct OneShot;
+
+pub struct FaithAnimationPlugin;
+
+impl Plugin for FaithAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FaithAnimations>()
+            .add_system_set(SystemSet::on_update(LoadingState::Play).with_system(return_to_swim));
+    }
+
+    fn name(&self) -> &str {
+        "FaithAnimationPlugin"
+    }
+}
+
+/// Benimator removes [`Play`] once a one-shot animation finishes.
+fn return_to_swim(
+    mut commands: Commands,
+    query: Query<Entity, (With<Faith>, With<OneShot>, Without<Play>)>,
+    animations: Res<FaithAnimations>,
+) {
+    for entity in query.iter() {
+        animations.play(&mut commands, entity, SWIM);
+    }
+}
@@ -0,0 +1,105 @@
+//! Sends commands to a running Trustworthy Dolphin over its control socket.
+//!
+//! ```sh
+//! dolphinctl spawn-fish 5
+//! dolphinctl say "build green"
+//! ```
+
+#[path = "../protocol.rs"]
+#[allow(dead_code)]
+mod protocol;
+
+use std::path::PathBuf;
+use std::process::exit;
+
+use protocol::{send, socket_path, ControlCommand, Trick};
+
+fn usage() -> String {
+    format!(
+        "\
+Sends commands to a running Trustworthy Dolphin.
+
+USAGE:
+    dolphinctl [--socket <PATH>] <COMMAND>
+
+COMMANDS:
+    spawn-fish [N]    Spawns N fishes right away [default: 1]
+    drain             Drains the water and exits
+    say <TEXT>...     Makes the dolphin say something
+    pause             Hides the aquarium and stops it
+    resume            Brings back a paused aquarium
+    trick <TRICK>     Makes the dolphin do a trick, one of
+                      {tricks}
+
+OPTIONS:
+        --socket <PATH>    Socket to connect to [env: DOLPHIN_SOCKET]
+    -h, --help             Print help information
+    -V, --version          Print version information",
+        tricks = Trick::names(),
+    )
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1).peekable();
+    let mut socket = None;
+    while let Some(arg) = args.next_if(|arg| arg.starts_with('-')) {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", usage());
+                return;
+            }
+            "-V" | "--version" => {
+                println!("dolphinctl {}", env!("CARGO_PKG_VERSION"));
+                return;
+            }
+            "--socket" => {
+                socket = Some(PathBuf::from(
+                    args.next().unwrap_or_else(|| fail("`--socket` requires a value")),
+                ))
+            }
+            _ => fail(&format!("unknown flag `{}`", arg)),
+        }
+    }
+
+    let command = match args.next().as_deref() {
+        Some("spawn-fish") => ControlCommand::SpawnFish {
+            count: match args.next() {
+                Some(count) => count
+                    .parse()
+                    .unwrap_or_else(|_| fail("`spawn-fish` takes a positive number of fishes")),
+                None => 1,
+            },
+        },
+        Some("drain") => ControlCommand::Drain,
+        Some("say") => ControlCommand::Say {
+            text: args.by_ref().collect::<Vec<_>>().join(" "),
+        },
+        Some("pause") => ControlCommand::Pause,
+        Some("resume") => ControlCommand::Resume,
+        Some("trick") => ControlCommand::Trick {
+            trick: args
+                .next()
+                .and_then(|trick| trick.parse().ok())
+                .unwrap_or_else(|| fail("`trick` takes the name of a trick")),
+        },
+        Some(command) => fail(&format!("unknown command `{}`", command)),
+        None => fail("no command given"),
+    };
+    if let Some(arg) = args.next() {
+        fail(&format!("unexpected argument `{}`", arg));
+    }
+    if let Err(err) = command.validate() {
+        fail(&err);
+    }
+
+    let socket = socket.unwrap_or_else(socket_path);
+    if let Err(err) = send(&socket, &command) {
+        eprintln!("error: {}", err);
+        exit(1);
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}\n\n{}", message, usage());
+    exit(2);
+}
@@ -0,0 +1,236 @@
+//! Schooling for fishes, kept free of any Bevy types
+//! like [`physics`](crate::physics).
+
+use std::collections::HashMap;
+
+use glam::Vec2;
+
+use crate::steering::Steering;
+
+/// How far away other fishes can be and still count as part of the school.
+pub const NEIGHBOR_RADIUS: f32 = 96.0;
+/// Fishes closer than this push each other apart.
+const SEPARATION_RADIUS: f32 = 36.0;
+
+const SEPARATION_WEIGHT: f32 = 2.0;
+const ALIGNMENT_WEIGHT: f32 = 0.6;
+const COHESION_WEIGHT: f32 = 0.3;
+/// How far fishes can stray from their cruising speed.
+const MIN_SPEED_FACTOR: f32 = 0.5;
+const MAX_SPEED_FACTOR: f32 = 1.5;
+
+/// Position and velocity of a single fish.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Boid {
+    pub position: Vec2,
+    pub velocity: Vec2,
+}
+
+/// Buckets points into a grid so that finding everything near
+/// a point only has to look at the cells around it, instead of
+/// comparing every pair of fishes.
+#[derive(Debug, Clone)]
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, position: Vec2) -> (i32, i32) {
+        let cell = (position / self.cell_size).floor();
+        (cell.x as i32, cell.y as i32)
+    }
+
+    pub fn insert(&mut self, index: usize, position: Vec2) {
+        self.cells.entry(self.cell(position)).or_default().push(index);
+    }
+
+    /// Everything in the cells that could be within `radius` of
+    /// `position`, which still needs to be filtered by distance.
+    pub fn candidates(&self, position: Vec2, radius: f32) -> impl Iterator<Item = usize> + '_ {
+        let (min_x, min_y) = self.cell(position - Vec2::splat(radius));
+        let (max_x, max_y) = self.cell(position + Vec2::splat(radius));
+        (min_x..=max_x)
+            .flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+}
+
+/// Which school a fish belongs to. Fishes only ever school
+/// with their own species, heading the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct School {
+    pub species: usize,
+    pub flip: bool,
+}
+
+/// Everything near `fishes[index]` in the same school, not including itself.
+/// `hash` has to have every fish in it, under its index in `fishes`.
+pub fn schoolmates<'a>(
+    fishes: &'a [(Boid, School)],
+    hash: &'a SpatialHash,
+    index: usize,
+) -> impl Iterator<Item = &'a Boid> + 'a {
+    let (boid, school) = fishes[index];
+    hash.candidates(boid.position, NEIGHBOR_RADIUS)
+        .filter(move |other| *other != index)
+        .map(move |other| &fishes[other])
+        .filter(move |(_, other_school)| *other_school == school)
+        .map(|(other, _)| other)
+}
+
+/// Classic separation, alignment, and cohesion, as an acceleration.
+/// `neighbors` shouldn't include `boid` itself.
+pub fn flocking_force<'a>(boid: &Boid, neighbors: impl IntoIterator<Item = &'a Boid>) -> Vec2 {
+    let mut separation = Vec2::ZERO;
+    let mut velocity_sum = Vec2::ZERO;
+    let mut position_sum = Vec2::ZERO;
+    let mut count = 0;
+
+    for neighbor in neighbors {
+        let offset = boid.position - neighbor.position;
+        let distance = offset.length();
+        if distance >= NEIGHBOR_RADIUS {
+            continue;
+        }
+        if distance < SEPARATION_RADIUS && distance > f32::EPSILON {
+            // push harder the closer they are
+            separation += offset / distance * (1.0 - distance / SEPARATION_RADIUS);
+        }
+        velocity_sum += neighbor.velocity;
+        position_sum += neighbor.position;
+        count += 1;
+    }
+    if count == 0 {
+        return Vec2::ZERO;
+    }
+
+    let alignment = velocity_sum / count as f32 - boid.velocity;
+    let cohesion = position_sum / count as f32 - boid.position;
+    separation * SEPARATION_WEIGHT * boid.velocity.length().max(1.0)
+        + alignment * ALIGNMENT_WEIGHT
+        + cohesion * COHESION_WEIGHT
+}
+
+/// The rest of a fish's school, which it keeps close to but not too close.
+pub struct Flock<'a> {
+    pub neighbors: Vec<&'a Boid>,
+}
+
+impl Steering for Flock<'_> {
+    fn force(&self, boid: &Boid) -> Vec2 {
+        flocking_force(boid, self.neighbors.iter().copied())
+    }
+}
+
+/// Keeps speed within reach of the fish's cruising speed. Unless
+/// `can_turn` is set, fishes never turn around completely.
+pub fn limit_velocity(velocity: Vec2, cruise_velocity: Vec2, can_turn: bool) -> Vec2 {
+    let cruise_speed = cruise_velocity.length();
+    let velocity = if !can_turn && velocity.x * cruise_velocity.x <= 0.0 {
+        Vec2::new(cruise_velocity.x * MIN_SPEED_FACTOR, velocity.y)
+    } else {
+        velocity
+    };
+    velocity.clamp_length(cruise_speed * MIN_SPEED_FACTOR, cruise_speed * MAX_SPEED_FACTOR)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    fn random_positions(count: usize) -> Vec<Vec2> {
+        let mut rng = StdRng::seed_from_u64(0);
+        (0..count)
+            .map(|_| Vec2::new(rng.gen_range(-500.0..500.0), rng.gen_range(-300.0..300.0)))
+            .collect()
+    }
+
+    #[test]
+    fn spatial_hash_matches_brute_force() {
+        let positions = random_positions(300);
+        let mut hash = SpatialHash::new(NEIGHBOR_RADIUS);
+        for (index, position) in positions.iter().enumerate() {
+            hash.insert(index, *position);
+        }
+
+        for radius in [20.0, NEIGHBOR_RADIUS, 250.0] {
+            for position in &positions {
+                let mut near = hash
+                    .candidates(*position, radius)
+                    .filter(|other| positions[*other].distance(*position) < radius)
+                    .collect::<Vec<_>>();
+                near.sort_unstable();
+                let brute_force = (0..positions.len())
+                    .filter(|other| positions[*other].distance(*position) < radius)
+                    .collect::<Vec<_>>();
+                assert_eq!(near, brute_force);
+            }
+        }
+    }
+
+    #[test]
+    fn schools_keep_to_themselves() {
+        let school = School {
+            species: 0,
+            flip: false,
+        };
+        let others = [
+            School {
+                species: 1,
+                flip: false,
+            },
+            School { species: 0, flip: true },
+        ];
+        // everyone mixed together, all close enough to school
+        let fishes = random_positions(60)
+            .into_iter()
+            .map(|position| position * 0.05)
+            .enumerate()
+            .map(|(index, position)| {
+                let boid = Boid {
+                    position,
+                    velocity: Vec2::new(40.0, index as f32 - 30.0),
+                };
+                (boid, if index % 3 == 0 { school } else { others[index % 2] })
+            })
+            .collect::<Vec<_>>();
+        let alone = fishes
+            .iter()
+            .filter(|(_, other)| *other == school)
+            .copied()
+            .collect::<Vec<_>>();
+
+        let hash_of = |fishes: &[(Boid, School)]| {
+            let mut hash = SpatialHash::new(NEIGHBOR_RADIUS);
+            for (index, (boid, _)) in fishes.iter().enumerate() {
+                hash.insert(index, boid.position);
+            }
+            hash
+        };
+        let (mixed_hash, alone_hash) = (hash_of(&fishes), hash_of(&alone));
+
+        let mixed = (0..fishes.len()).filter(|index| fishes[*index].1 == school);
+        for (index, alone_index) in mixed.zip(0..alone.len()) {
+            let mates = schoolmates(&fishes, &mixed_hash, index).collect::<Vec<_>>();
+            assert_eq!(mates.len(), alone.len() - 1);
+
+            let boid = &fishes[index].0;
+            let force = flocking_force(boid, mates);
+            let force_alone = flocking_force(boid, schoolmates(&alone, &alone_hash, alone_index));
+            assert_eq!(force, force_alone);
+        }
+    }
+}
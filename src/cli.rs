@@ -0,0 +1,431 @@
+//! Command-line options, parsed once at startup
+//! and then stored as the [`Options`] resource.
+
+use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::headless::{HeadlessOptions, DEFAULT_FRAMES, DEFAULT_OUT_DIR};
+use crate::protocol::Trick;
+use crate::settings::{CONFIG_DIR, CONFIG_FILE};
+
+/// Which monitors get a tank of their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Monitors {
+    Primary,
+    All,
+    /// Every monitor, with fishes swimming from one tank into the next.
+    Span,
+    Index(usize),
+}
+
+impl FromStr for Monitors {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "primary" => Ok(Self::Primary),
+            "all" => Ok(Self::All),
+            "span" => Ok(Self::Span),
+            _ => s.parse().map(Self::Index).map_err(|_| ()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Show only on the desktop rather than over all other apps.
+    pub desktop: bool,
+    /// Act as the desktop window itself. Only does anything on X11.
+    pub desktop_window: bool,
+    pub interactive: bool,
+    pub monitors: Monitors,
+    /// Leave panels and taskbars uncovered.
+    pub work_area: bool,
+    /// Overrides the config file's path.
+    pub config: Option<PathBuf>,
+    // these override the matching settings in the config file
+    pub max_bubbles: Option<u32>,
+    pub max_fishes: Option<u32>,
+    pub pod_size: Option<u32>,
+    pub wrap_around: bool,
+    pub speed: Option<f64>,
+    pub seed: Option<u64>,
+    /// Multiplies the size of every sprite, on top of the config file's `sprite_scale`.
+    pub scale: f32,
+    /// Opacity of the whole window, from 0 to 1.
+    pub opacity: f32,
+    /// Trick the dolphin does as soon as it shows up.
+    pub trick: Option<Trick>,
+    pub headless: Option<HeadlessOptions>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            desktop: false,
+            desktop_window: false,
+            interactive: false,
+            monitors: Monitors::Primary,
+            work_area: false,
+            config: None,
+            max_bubbles: None,
+            max_fishes: None,
+            pod_size: None,
+            wrap_around: false,
+            speed: None,
+            seed: None,
+            scale: 1.0,
+            opacity: 1.0,
+            trick: None,
+            headless: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CliError {
+    /// `--help` was passed, so print the usage instead of running.
+    Help,
+    /// `--version` was passed, so print the version instead of running.
+    Version,
+    UnknownFlag(String),
+    MissingValue(String),
+    UnexpectedValue(String),
+    InvalidValue {
+        flag: String,
+        value: String,
+        expected: String,
+    },
+    /// `--frames` or `--out` were passed without `--headless`.
+    RequiresHeadless(String),
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Help => write!(f, "{}", help()),
+            Self::Version => write!(f, "{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            Self::UnknownFlag(flag) => write!(f, "unknown flag `{}`", flag),
+            Self::MissingValue(flag) => write!(f, "`{}` requires a value", flag),
+            Self::UnexpectedValue(flag) => write!(f, "`{}` doesn't take a value", flag),
+            Self::InvalidValue { flag, value, expected } => {
+                write!(f, "invalid value `{}` for `{}`, expected {}", value, flag, expected)
+            }
+            Self::RequiresHeadless(flag) => write!(f, "`{}` can only be used with `--headless`", flag),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl Options {
+    /// Parses the process's arguments, printing the help text, version, or
+    /// an error and exiting if the aquarium shouldn't actually be started.
+    pub fn from_env() -> Self {
+        match Self::parse(std::env::args().skip(1)) {
+            Ok(options) => options,
+            Err(message @ (CliError::Help | CliError::Version)) => {
+                println!("{}", message);
+                std::process::exit(0);
+            }
+            Err(error) => {
+                eprintln!("error: {}\n\nFor more information try `--help`", error);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    /// Parses `args`, not including the program name.
+    ///
+    /// Long flags can be written with either one or two dashes,
+    /// and their values can be passed as `--flag value` or `--flag=value`.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, CliError> {
+        let mut options = Self::default();
+        let mut headless = false;
+        let mut frames = None;
+        let mut out = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg.clone(), None),
+            };
+            let name = flag.strip_prefix("--").or_else(|| flag.strip_prefix('-'));
+
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| CliError::MissingValue(flag.clone()))
+            };
+            // switches don't take values, so `--desktop=no` is a mistake
+            let switch = || {
+                if inline_value.is_some() {
+                    Err(CliError::UnexpectedValue(flag.clone()))
+                } else {
+                    Ok(true)
+                }
+            };
+
+            match name {
+                Some("h" | "help") => return Err(CliError::Help),
+                Some("V" | "version") => return Err(CliError::Version),
+                Some("d" | "desktop") => options.desktop = switch()?,
+                Some("desktop-window") => options.desktop_window = switch()?,
+                Some("i" | "interactive") => options.interactive = switch()?,
+                Some("monitors") => {
+                    options.monitors = parse_value(&flag, value()?, "`primary`, `all`, `span`, or a monitor index")?
+                }
+                Some("work-area") => options.work_area = switch()?,
+                Some("config") => options.config = Some(PathBuf::from(value()?)),
+                Some("max-bubbles") => {
+                    options.max_bubbles = Some(parse_value(&flag, value()?, "a non-negative integer")?)
+                }
+                Some("max-fishes") => {
+                    options.max_fishes = Some(parse_value(&flag, value()?, "a non-negative integer")?)
+                }
+                Some("pod-size") => options.pod_size = Some(parse_positive(&flag, value()?)?),
+                Some("wrap-around") => options.wrap_around = switch()?,
+                Some("speed") => options.speed = Some(parse_positive(&flag, value()?)?),
+                Some("seed") => options.seed = Some(parse_value(&flag, value()?, "an unsigned 64-bit integer")?),
+                Some("scale") => options.scale = parse_positive(&flag, value()?)?,
+                Some("opacity") => {
+                    let raw = value()?;
+                    let opacity: f32 = parse_value(&flag, raw.clone(), "a number from 0 to 1")?;
+                    if !(0.0..=1.0).contains(&opacity) {
+                        return Err(invalid_value(&flag, raw, "a number from 0 to 1"));
+                    }
+                    options.opacity = opacity;
+                }
+                Some("trick") => options.trick = Some(parse_value(&flag, value()?, &Trick::names())?),
+                Some("headless") => headless = switch()?,
+                Some("frames") => frames = Some((flag.clone(), parse_positive(&flag, value()?)?)),
+                Some("out") => out = Some((flag.clone(), PathBuf::from(value()?))),
+                _ => return Err(CliError::UnknownFlag(arg)),
+            }
+        }
+
+        if headless {
+            options.headless = Some(HeadlessOptions {
+                frames: frames.map_or(DEFAULT_FRAMES, |(_, frames)| frames),
+                out: out.map_or_else(|| PathBuf::from(DEFAULT_OUT_DIR), |(_, out)| out),
+            });
+        } else if let Some((flag, _)) = frames {
+            return Err(CliError::RequiresHeadless(flag));
+        } else if let Some((flag, _)) = out {
+            return Err(CliError::RequiresHeadless(flag));
+        }
+
+        Ok(options)
+    }
+
+    /// Whether the window should stay below other apps.
+    pub fn on_desktop(&self) -> bool {
+        self.desktop || self.desktop_window
+    }
+}
+
+fn invalid_value(flag: &str, value: String, expected: &str) -> CliError {
+    CliError::InvalidValue {
+        flag: flag.to_string(),
+        value,
+        expected: expected.to_string(),
+    }
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: String, expected: &str) -> Result<T, CliError> {
+    value.parse().map_err(|_| invalid_value(flag, value, expected))
+}
+
+fn parse_positive<T: FromStr + Into<f64> + Copy>(flag: &str, value: String) -> Result<T, CliError> {
+    const EXPECTED: &str = "a positive number";
+    let parsed: T = parse_value(flag, value.clone(), EXPECTED)?;
+    let as_f64 = parsed.into();
+    if as_f64 > 0.0 && as_f64.is_finite() {
+        Ok(parsed)
+    } else {
+        Err(invalid_value(flag, value, EXPECTED))
+    }
+}
+
+fn help() -> String {
+    let defaults = Options::default();
+    format!(
+        "\
+{name} {version}
+A playful app that adds aquatic spice to desktops.
+
+USAGE:
+    {name} [OPTIONS]
+
+OPTIONS:
+    -d, --desktop              Show only on the desktop rather than over all other apps
+        --desktop-window       Act as the desktop window itself (X11 only)
+    -i, --interactive          Let the dolphin be bonked, dragged, and thrown
+        --monitors <MONITORS>  Monitors to show on: `primary`, `all`, `span` to let fishes swim
+                               between them, or a monitor index [default: primary]
+        --work-area            Fit tanks to the work area rather than covering panels and taskbars
+        --config <FILE>        Config file to use [default: $XDG_CONFIG_HOME/{config_dir}/{config_file}]
+        --max-bubbles <N>      Maximum number of bubble columns, overriding the config file
+        --max-fishes <N>       Maximum number of fishes, overriding the config file
+        --pod-size <N>         Number of dolphins, overriding the config file
+        --wrap-around          Let dolphins swim off one side and back in on the other
+        --speed <SPEED>        How much faster than real time the dolphin moves, overriding the config file
+        --seed <SEED>          Seed for the random number generator [env: {seed_env}]
+        --scale <SCALE>        Multiplies the size of every sprite [default: {scale}]
+        --opacity <OPACITY>    Opacity of the whole window, from 0 to 1 [default: {opacity}]
+        --trick <TRICK>        Trick for the dolphin to start with, one of
+                               {tricks}
+        --headless             Render frames to PNGs without a window or GPU
+        --frames <N>           Number of frames to render in headless mode [default: {frames}]
+        --out <DIR>            Directory to write headless frames to [default: {out}]
+    -h, --help                 Print help information
+    -V, --version              Print version information",
+        name = env!("CARGO_PKG_NAME"),
+        version = env!("CARGO_PKG_VERSION"),
+        config_dir = CONFIG_DIR,
+        config_file = CONFIG_FILE,
+        seed_env = crate::rng::SEED_ENV_VAR,
+        scale = defaults.scale,
+        opacity = defaults.opacity,
+        tricks = Trick::names(),
+        frames = DEFAULT_FRAMES,
+        out = DEFAULT_OUT_DIR,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, CliError> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn no_flags_gives_defaults() {
+        let options = parse(&[]).unwrap();
+        assert!(!options.desktop && !options.interactive && !options.wrap_around);
+        assert_eq!(options.monitors, Monitors::Primary);
+        assert_eq!(options.scale, 1.0);
+        assert!(options.headless.is_none());
+    }
+
+    #[test]
+    fn parses_switches() {
+        assert!(parse(&["-d"]).unwrap().desktop);
+        assert!(parse(&["--desktop"]).unwrap().desktop);
+        assert!(parse(&["--desktop-window"]).unwrap().desktop_window);
+        assert!(parse(&["-i"]).unwrap().interactive);
+        assert!(parse(&["-interactive"]).unwrap().interactive);
+        assert!(parse(&["--work-area"]).unwrap().work_area);
+        assert!(parse(&["--wrap-around"]).unwrap().wrap_around);
+        assert_eq!(parse(&["-h"]).unwrap_err(), CliError::Help);
+        assert_eq!(parse(&["--help"]).unwrap_err(), CliError::Help);
+        assert_eq!(parse(&["-V"]).unwrap_err(), CliError::Version);
+        assert_eq!(parse(&["--version"]).unwrap_err(), CliError::Version);
+        assert_eq!(
+            parse(&["--desktop=yes"]).unwrap_err(),
+            CliError::UnexpectedValue("--desktop".to_string())
+        );
+    }
+
+    #[test]
+    fn parses_values() {
+        assert_eq!(parse(&["--monitors", "all"]).unwrap().monitors, Monitors::All);
+        assert_eq!(parse(&["--monitors=span"]).unwrap().monitors, Monitors::Span);
+        assert_eq!(parse(&["--monitors", "2"]).unwrap().monitors, Monitors::Index(2));
+        assert_eq!(
+            parse(&["--config", "tank.toml"]).unwrap().config,
+            Some(PathBuf::from("tank.toml"))
+        );
+        assert_eq!(parse(&["--max-bubbles", "0"]).unwrap().max_bubbles, Some(0));
+        assert_eq!(parse(&["--max-fishes", "8"]).unwrap().max_fishes, Some(8));
+        assert_eq!(parse(&["--pod-size", "3"]).unwrap().pod_size, Some(3));
+        assert_eq!(parse(&["--speed", "2.5"]).unwrap().speed, Some(2.5));
+        assert_eq!(parse(&["--seed", "42"]).unwrap().seed, Some(42));
+        assert_eq!(parse(&["--scale", "2"]).unwrap().scale, 2.0);
+        assert_eq!(parse(&["--opacity", "0.5"]).unwrap().opacity, 0.5);
+        for trick in Trick::ALL {
+            assert_eq!(parse(&["--trick", trick.name()]).unwrap().trick, Some(trick));
+        }
+    }
+
+    #[test]
+    fn parses_headless_options() {
+        let headless = parse(&["--headless"]).unwrap().headless.unwrap();
+        assert_eq!(headless.frames, DEFAULT_FRAMES);
+        assert_eq!(headless.out, PathBuf::from(DEFAULT_OUT_DIR));
+
+        let headless = parse(&["--headless", "--frames", "10", "--out=shots"])
+            .unwrap()
+            .headless
+            .unwrap();
+        assert_eq!(headless.frames, 10);
+        assert_eq!(headless.out, PathBuf::from("shots"));
+    }
+
+    #[test]
+    fn headless_options_require_headless() {
+        assert_eq!(
+            parse(&["--frames", "10"]).unwrap_err(),
+            CliError::RequiresHeadless("--frames".to_string())
+        );
+        assert_eq!(
+            parse(&["--out", "shots"]).unwrap_err(),
+            CliError::RequiresHeadless("--out".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        for args in [
+            &["--monitors", "some"][..],
+            &["--max-fishes", "-1"],
+            &["--pod-size", "0"],
+            &["--speed", "0"],
+            &["--scale", "inf"],
+            &["--opacity", "1.5"],
+            &["--headless", "--frames", "0"],
+            &["--trick", "somersault"],
+        ] {
+            assert!(
+                matches!(parse(args), Err(CliError::InvalidValue { .. })),
+                "accepted {:?}",
+                args
+            );
+        }
+        assert_eq!(
+            parse(&["--seed"]).unwrap_err(),
+            CliError::MissingValue("--seed".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_flags() {
+        assert_eq!(
+            parse(&["--fish"]).unwrap_err(),
+            CliError::UnknownFlag("--fish".to_string())
+        );
+        assert_eq!(
+            parse(&["dolphin"]).unwrap_err(),
+            CliError::UnknownFlag("dolphin".to_string())
+        );
+        assert_eq!(
+            parse(&["--fish=3"]).unwrap_err(),
+            CliError::UnknownFlag("--fish=3".to_string())
+        );
+    }
+
+    #[test]
+    fn lists_every_trick() {
+        let error = parse(&["--trick", "somersault"]).unwrap_err().to_string();
+        let help = help();
+        for trick in Trick::ALL {
+            assert!(error.contains(&format!("`{}`", trick)), "{}", error);
+            assert!(help.contains(&format!("`{}`", trick)), "{}", help);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Simulated time, kept apart from Bevy's wall clock so that it can be
+//! stepped by a fixed amount when rendering frames headlessly.
+
+use std::time::Duration;
+
+use bevy::prelude::{App, CoreStage, Plugin, Res, ResMut, Time};
+
+/// Clock that drives all of the aquarium's animation.
+///
+/// Normally this just follows Bevy's [`Time`], but it can
+/// also be stepped by a fixed amount every frame so that
+/// headless runs are reproducible. It stands still until it's
+/// [started](Self::start), so that however long loading takes
+/// doesn't change what happens afterwards, and while paused.
+#[derive(Debug, Default)]
+pub struct SimClock {
+    elapsed: Duration,
+    delta: Duration,
+    fixed_step: Option<Duration>,
+    started: bool,
+    paused: bool,
+}
+
+impl SimClock {
+    pub fn fixed(step: Duration) -> Self {
+        Self {
+            fixed_step: Some(step),
+            ..Self::default()
+        }
+    }
+
+    /// Total simulated time since the clock was started.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn start(&mut self) {
+        self.started = true;
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Simulated time since the last frame, in seconds.
+    pub fn delta_seconds_f64(&self) -> f64 {
+        self.delta.as_secs_f64()
+    }
+}
+
+pub struct ClockPlugin;
+
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SimClock>()
+            .add_system_to_stage(CoreStage::PreUpdate, tick_clock);
+    }
+
+    fn name(&self) -> &str {
+        "ClockPlugin"
+    }
+}
+
+fn tick_clock(time: Res<Time>, mut clock: ResMut<SimClock>) {
+    let delta = if clock.paused || !clock.started {
+        Duration::ZERO
+    } else {
+        clock.fixed_step.unwrap_or_else(|| time.delta())
+    };
+    clock.delta = delta;
+    clock.elapsed += delta;
+}
+
+pub fn start_clock(mut clock: ResMut<SimClock>) {
+    clock.start();
+}
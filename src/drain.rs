@@ -0,0 +1,161 @@
+//! Draining the tank before exiting, so that quitting plays the
+//! fill animation in reverse instead of cutting off instantly.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::window::WindowCloseRequested;
+
+use crate::clock::SimClock;
+use crate::protocol::ControlCommand;
+use crate::tank::{TankId, Tanks};
+use crate::window::WindowSize;
+use crate::{fill_curve, Bubbles, Faith, Fish, LoadingState, Water};
+
+/// How long the water takes to drain, in seconds.
+const DRAIN_TIME: f64 = 2.0;
+/// Exit even if something's still on screen after this long.
+const MAX_DRAIN_TIME: f64 = 6.0;
+/// How much faster fishes swim while fleeing the draining tank.
+const FISH_FLEE_MULTIPLIER: f32 = 4.0;
+
+/// Set from the signal handler thread when
+/// the process is asked to terminate.
+#[derive(Debug, Default, Clone)]
+struct TerminateRequested(Arc<AtomicBool>);
+
+pub struct DrainPlugin;
+
+impl Plugin for DrainPlugin {
+    fn build(&self, app: &mut App) {
+        let terminate = TerminateRequested::default();
+        let flag = terminate.0.clone();
+        if let Err(err) = ctrlc::set_handler(move || {
+            // a second Ctrl-C skips the animation
+            if flag.swap(true, Ordering::SeqCst) {
+                std::process::exit(130);
+            }
+        }) {
+            warn!("Couldn't handle termination signals: {}", err);
+        }
+
+        app.insert_resource(terminate)
+            .add_system_to_stage(CoreStage::PreUpdate, start_draining)
+            .add_system_set(SystemSet::on_enter(LoadingState::Draining).with_system(enter_draining))
+            .add_system_set(
+                SystemSet::on_update(LoadingState::Draining)
+                    .with_system(drain_water.label("water_level"))
+                    .with_system(drain_fishes)
+                    .with_system(finish_draining),
+            );
+    }
+
+    fn name(&self) -> &str {
+        "DrainPlugin"
+    }
+}
+
+fn start_draining(
+    terminate: Res<TerminateRequested>,
+    mut control: EventReader<ControlCommand>,
+    mut close_requests: ResMut<Events<WindowCloseRequested>>,
+    keys: Res<Input<KeyCode>>,
+    mut state: ResMut<State<LoadingState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    // take close requests before Bevy's own handler sees them and exits,
+    // and drain every tank when any of them is closed
+    let close_requested = close_requests.drain().count() > 0;
+    let drain_requested = control.iter().any(|command| *command == ControlCommand::Drain);
+    let requested =
+        terminate.0.load(Ordering::SeqCst) || close_requested || drain_requested || keys.just_pressed(KeyCode::Escape);
+
+    if requested {
+        match state.current() {
+            // there's no water to drain yet
+            LoadingState::Loading => exit.send(AppExit),
+            LoadingState::Draining => {}
+            _ => {
+                let _ = state.set(LoadingState::Draining);
+            }
+        }
+    }
+}
+
+fn enter_draining(
+    mut commands: Commands,
+    mut water_query: Query<&mut Water>,
+    mut faith_query: Query<&mut Faith>,
+    bubbles_query: Query<Entity, With<Bubbles>>,
+    fish_query: Query<(Entity, &Visibility), With<Fish>>,
+    clock: Res<SimClock>,
+) {
+    for mut water in water_query.iter_mut() {
+        water.start_time = clock.elapsed();
+        water.drain_from = water.water_level;
+    }
+
+    // let go of the dolphin so that it can fall
+    for mut faith in faith_query.iter_mut() {
+        faith.sim.held = false;
+    }
+
+    // bubbles would be left floating in midair
+    for entity in bubbles_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    // fishes that haven't shown up yet never will
+    for (entity, visibility) in fish_query.iter() {
+        if !visibility.is_visible {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// The reverse of `fill_water`, starting from wherever the water was.
+fn drain_water(mut query: Query<&mut Water>, clock: Res<SimClock>) {
+    for mut water in query.iter_mut() {
+        let anim_time = ((clock.elapsed() - water.start_time).as_secs_f64() / DRAIN_TIME).min(1.0);
+        water.water_level = water.drain_from + fill_curve(1.0 - anim_time) - 1.0;
+    }
+}
+
+fn drain_fishes(
+    mut commands: Commands,
+    mut query: Query<(Entity, &Fish, &TankId, &mut Transform)>,
+    tanks: Res<Tanks>,
+    clock: Res<SimClock>,
+) {
+    for (entity, fish, tank, mut transform) in query.iter_mut() {
+        transform.translation.x += fish.speed as f32 * FISH_FLEE_MULTIPLIER * clock.delta_seconds_f64() as f32;
+        let (left, right) = tanks.lane(*tank);
+        if transform.translation.x < left || transform.translation.x > right {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Exits once the water's gone and nothing's left on screen.
+fn finish_draining(
+    water_query: Query<&Water>,
+    faith_query: Query<&Faith>,
+    fish_query: Query<(), With<Fish>>,
+    window_size: Res<WindowSize>,
+    clock: Res<SimClock>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let drain_time = water_query.iter().next().map_or(MAX_DRAIN_TIME, |water| {
+        (clock.elapsed() - water.start_time).as_secs_f64()
+    });
+    let bottom = -window_size.0.y as f64 / 2.0;
+
+    let drained = drain_time >= DRAIN_TIME
+        && fish_query.iter().next().is_none()
+        && faith_query.iter().all(|faith| faith.sim.position.y < bottom * 1.5);
+    if drained || drain_time >= MAX_DRAIN_TIME {
+        exit.send(AppExit);
+    }
+}
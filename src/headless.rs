@@ -0,0 +1,353 @@
+//! Windowless mode that renders frames on the CPU and dumps them to PNGs.
+//!
+//! Useful for CI screenshots and snapshot tests, since it needs
+//! neither a monitor nor a GPU.
+
+use std::cmp::Ordering;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bevy::app::{AppExit, PluginGroupBuilder, ScheduleRunnerPlugin, ScheduleRunnerSettings};
+use bevy::asset::AssetPlugin;
+use bevy::audio::AudioPlugin;
+use bevy::core_pipeline::CorePipelinePlugin;
+use bevy::math::{const_uvec2, UVec2, Vec4Swizzles};
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, VertexAttributeValues};
+use bevy::render::render_resource::AddressMode;
+use bevy::render::RenderPlugin;
+use bevy::sprite::{Mesh2dHandle, Rect, SpritePlugin};
+use bevy::text::TextPlugin;
+use bevy::ui::UiPlugin;
+use bevy::winit::WinitPlugin;
+
+use crate::cli::Options;
+use crate::clock::SimClock;
+use crate::tank::Tanks;
+use crate::util::{texel, to_affine2};
+use crate::window::WindowSize;
+use crate::LoadingState;
+
+pub const RESOLUTION: UVec2 = const_uvec2!([1280, 720]);
+pub const FRAME_TIME: f64 = 1.0 / 60.0;
+
+pub const DEFAULT_FRAMES: u32 = 60;
+pub const DEFAULT_OUT_DIR: &str = "frames";
+
+#[derive(Debug, Clone)]
+pub struct HeadlessOptions {
+    pub frames: u32,
+    pub out: PathBuf,
+}
+
+/// Swaps out every plugin that needs a window or a GPU for the [`HeadlessPlugin`].
+pub fn configure_plugins(group: &mut PluginGroupBuilder, options: HeadlessOptions) {
+    group
+        .disable::<WinitPlugin>()
+        .disable::<RenderPlugin>()
+        .disable::<CorePipelinePlugin>()
+        .disable::<SpritePlugin>()
+        .disable::<TextPlugin>()
+        .disable::<UiPlugin>()
+        .disable::<AudioPlugin>()
+        .add_after::<AssetPlugin, _>(HeadlessPlugin(options));
+}
+
+pub struct HeadlessPlugin(pub HeadlessOptions);
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        std::fs::create_dir_all(&self.0.out).expect("Couldn't create headless output directory");
+
+        // normally registered by the render and sprite plugins
+        app.add_asset::<Image>()
+            .add_asset::<Mesh>()
+            .add_asset::<TextureAtlas>()
+            .add_asset::<ColorMaterial>();
+
+        app.insert_resource(self.0.clone())
+            .insert_resource(ScheduleRunnerSettings::run_loop(Duration::ZERO))
+            .insert_resource(WindowSize(RESOLUTION.as_vec2()))
+            .insert_resource(Tanks::single(RESOLUTION.as_vec2()))
+            .insert_resource(SimClock::fixed(Duration::from_secs_f64(FRAME_TIME)))
+            .add_plugin(ScheduleRunnerPlugin::default())
+            .add_system_to_stage(CoreStage::Last, render_frame);
+    }
+
+    fn name(&self) -> &str {
+        "HeadlessPlugin"
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn render_frame(
+    mut frame: Local<u32>,
+    options: Res<HeadlessOptions>,
+    cli_options: Res<Options>,
+    state: Res<State<LoadingState>>,
+    images: Res<Assets<Image>>,
+    atlases: Res<Assets<TextureAtlas>>,
+    meshes: Res<Assets<Mesh>>,
+    materials: Res<Assets<ColorMaterial>>,
+    sprite_query: Query<(&Sprite, &Handle<Image>, &Visibility, &GlobalTransform)>,
+    atlas_sprite_query: Query<(
+        &TextureAtlasSprite,
+        &Handle<TextureAtlas>,
+        &Visibility,
+        &GlobalTransform,
+    )>,
+    mesh_query: Query<(&Mesh2dHandle, &Handle<ColorMaterial>, &Visibility, &GlobalTransform)>,
+    mut exit: EventWriter<AppExit>,
+) {
+    // frames before the assets are loaded would just be
+    // blank, and how many there are isn't deterministic
+    if *state.current() == LoadingState::Loading {
+        return;
+    }
+
+    let mut canvas = Canvas::new(RESOLUTION);
+
+    // collect everything first so that it can be drawn back to front
+    let mut draw_calls: Vec<(f32, Box<dyn Fn(&mut Canvas) + '_>)> = Vec::new();
+    for (sprite, image, visibility, transform) in sprite_query.iter() {
+        if !visibility.is_visible {
+            continue;
+        }
+        let tint = Vec4::from(sprite.color.as_rgba_f32());
+        if let Some(image) = images.get(image) {
+            let size = sprite.custom_size.unwrap_or_else(|| image.size());
+            let rect = Rect {
+                min: Vec2::ZERO,
+                max: image.size(),
+            };
+            draw_calls.push((
+                transform.translation.z,
+                Box::new(move |canvas| {
+                    canvas.draw_sprite(transform, size, |uv| {
+                        let uv = flip_uv(uv, sprite.flip_x, sprite.flip_y);
+                        sample_rect(image, rect, uv) * tint
+                    })
+                }),
+            ));
+        } else if let (true, Some(size)) = (*image == Handle::default(), sprite.custom_size) {
+            // the default image is plain white, but it's only
+            // added by the render plugin so just use the tint
+            draw_calls.push((
+                transform.translation.z,
+                Box::new(move |canvas| canvas.draw_sprite(transform, size, |_| tint)),
+            ));
+        }
+    }
+    for (sprite, atlas, visibility, transform) in atlas_sprite_query.iter() {
+        let atlas = atlases.get(atlas);
+        let image = atlas.and_then(|atlas| images.get(&atlas.texture));
+        if let (true, Some(atlas), Some(image)) = (visibility.is_visible, atlas, image) {
+            let rect = atlas.textures[sprite.index];
+            let size = sprite.custom_size.unwrap_or_else(|| rect.size());
+            let tint = Vec4::from(sprite.color.as_rgba_f32());
+            draw_calls.push((
+                transform.translation.z,
+                Box::new(move |canvas| {
+                    canvas.draw_sprite(transform, size, |uv| {
+                        let uv = flip_uv(uv, sprite.flip_x, sprite.flip_y);
+                        sample_rect(image, rect, uv) * tint
+                    })
+                }),
+            ));
+        }
+    }
+    for (mesh, material, visibility, transform) in mesh_query.iter() {
+        let mesh = meshes.get(&mesh.0);
+        let material = materials.get(material);
+        if let (true, Some(mesh), Some(material)) = (visibility.is_visible, mesh, material) {
+            let image = material.texture.as_ref().and_then(|texture| images.get(texture));
+            let tint = Vec4::from(material.color.as_rgba_f32());
+            draw_calls.push((
+                transform.translation.z,
+                Box::new(move |canvas| {
+                    canvas.draw_mesh(transform, mesh, |uv| match image {
+                        Some(image) => sample_wrapped(image, uv) * tint,
+                        None => tint,
+                    })
+                }),
+            ));
+        }
+    }
+
+    draw_calls.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+    for (_, draw) in draw_calls {
+        draw(&mut canvas);
+    }
+
+    let path = options.out.join(format!("frame_{:05}.png", *frame));
+    image::save_buffer(
+        &path,
+        &canvas.to_rgba8(cli_options.opacity),
+        RESOLUTION.x,
+        RESOLUTION.y,
+        image::ColorType::Rgba8,
+    )
+    .unwrap_or_else(|err| panic!("Couldn't write {}: {}", path.display(), err));
+
+    *frame += 1;
+    if *frame >= options.frames {
+        exit.send(AppExit);
+    }
+}
+
+/// Premultiplied RGBA framebuffer with the same
+/// y-up, centered coordinates as the 2d camera.
+struct Canvas {
+    size: UVec2,
+    pixels: Vec<Vec4>,
+}
+
+impl Canvas {
+    fn new(size: UVec2) -> Self {
+        Self {
+            size,
+            pixels: vec![Vec4::ZERO; (size.x * size.y) as usize],
+        }
+    }
+
+    fn pixel_to_world(&self, x: u32, y: u32) -> Vec2 {
+        Vec2::new(
+            x as f32 + 0.5 - self.size.x as f32 / 2.0,
+            self.size.y as f32 / 2.0 - (y as f32 + 0.5),
+        )
+    }
+
+    fn world_to_pixel(&self, pos: Vec2) -> Vec2 {
+        Vec2::new(pos.x + self.size.x as f32 / 2.0, self.size.y as f32 / 2.0 - pos.y)
+    }
+
+    /// Iterates over the pixels covered by a world space bounding box.
+    fn pixels_in(&self, corners: &[Vec2]) -> impl Iterator<Item = (u32, u32)> {
+        let (min, max) = corners
+            .iter()
+            .fold((Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)), |acc, &x| {
+                let pixel = self.world_to_pixel(x);
+                (acc.0.min(pixel), acc.1.max(pixel))
+            });
+        let min = min.floor().max(Vec2::ZERO).as_uvec2();
+        let max = max.ceil().min(self.size.as_vec2()).as_uvec2();
+        (min.y..max.y).flat_map(move |y| (min.x..max.x).map(move |x| (x, y)))
+    }
+
+    /// Composites a straight alpha color over the pixel at `(x, y)`.
+    fn blend(&mut self, x: u32, y: u32, color: Vec4) {
+        let src = (color.xyz() * color.w).extend(color.w);
+        let dst = &mut self.pixels[(y * self.size.x + x) as usize];
+        *dst = src + *dst * (1.0 - color.w);
+    }
+
+    /// Draws a `size` sized quad centered on `transform`,
+    /// with `sample` giving the color at each UV coordinate.
+    fn draw_sprite(&mut self, transform: &GlobalTransform, size: Vec2, sample: impl Fn(Vec2) -> Vec4) {
+        let affine = to_affine2(transform);
+        let inverse = affine.inverse();
+        let half_size = size / 2.0;
+        let corners = [
+            Vec2::new(-half_size.x, -half_size.y),
+            Vec2::new(-half_size.x, half_size.y),
+            Vec2::new(half_size.x, half_size.y),
+            Vec2::new(half_size.x, -half_size.y),
+        ]
+        .map(|x| affine.transform_point2(x));
+
+        for (x, y) in self.pixels_in(&corners).collect::<Vec<_>>() {
+            let local = inverse.transform_point2(self.pixel_to_world(x, y));
+            if local.abs().cmple(half_size).all() {
+                let uv = Vec2::new(local.x / size.x + 0.5, 0.5 - local.y / size.y);
+                self.blend(x, y, sample(uv));
+            }
+        }
+    }
+
+    /// Draws a triangle list mesh, with `sample`
+    /// giving the color at each UV coordinate.
+    fn draw_mesh(&mut self, transform: &GlobalTransform, mesh: &Mesh, sample: impl Fn(Vec2) -> Vec4) {
+        let affine = to_affine2(transform);
+        let positions = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float32x3(positions)) => positions,
+            _ => return,
+        };
+        let uvs = match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+            Some(VertexAttributeValues::Float32x2(uvs)) => uvs,
+            _ => return,
+        };
+        let indices = match mesh.indices() {
+            Some(Indices::U16(indices)) => indices.iter().map(|&i| i as usize).collect::<Vec<_>>(),
+            Some(Indices::U32(indices)) => indices.iter().map(|&i| i as usize).collect::<Vec<_>>(),
+            None => (0..positions.len()).collect::<Vec<_>>(),
+        };
+
+        for triangle in indices.chunks_exact(3) {
+            let corners = [0, 1, 2].map(|i| affine.transform_point2(Vec2::from_slice(&positions[triangle[i]])));
+            let tex_coords = [0, 1, 2].map(|i| Vec2::from(uvs[triangle[i]]));
+
+            let area = (corners[1] - corners[0]).perp_dot(corners[2] - corners[0]);
+            if area.abs() <= f32::EPSILON {
+                continue;
+            }
+            for (x, y) in self.pixels_in(&corners).collect::<Vec<_>>() {
+                let pos = self.pixel_to_world(x, y);
+                let w0 = (corners[2] - corners[1]).perp_dot(pos - corners[1]) / area;
+                let w1 = (corners[0] - corners[2]).perp_dot(pos - corners[2]) / area;
+                let w2 = 1.0 - w0 - w1;
+                if w0 >= 0.0 && w1 >= 0.0 && w2 >= 0.0 {
+                    let uv = tex_coords[0] * w0 + tex_coords[1] * w1 + tex_coords[2] * w2;
+                    self.blend(x, y, sample(uv));
+                }
+            }
+        }
+    }
+
+    /// Converts back to straight alpha 8-bit RGBA,
+    /// fading the whole frame out by `opacity`.
+    fn to_rgba8(&self, opacity: f32) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|pixel| {
+                let color = if pixel.w > 0.0 {
+                    (pixel.xyz() / pixel.w).extend(pixel.w * opacity)
+                } else {
+                    Vec4::ZERO
+                };
+                (color.clamp(Vec4::ZERO, Vec4::ONE) * 255.0)
+                    .round()
+                    .as_uvec4()
+                    .to_array()
+            })
+            .map(|x| x as u8)
+            .collect()
+    }
+}
+
+fn flip_uv(uv: Vec2, flip_x: bool, flip_y: bool) -> Vec2 {
+    Vec2::new(
+        if flip_x { 1.0 - uv.x } else { uv.x },
+        if flip_y { 1.0 - uv.y } else { uv.y },
+    )
+}
+
+/// Nearest-neighbour samples the `rect` region of an image.
+fn sample_rect(image: &Image, rect: Rect, uv: Vec2) -> Vec4 {
+    let pos = rect.min + uv.clamp(Vec2::ZERO, Vec2::ONE) * rect.size();
+    let pos = pos.floor().min(rect.max - Vec2::ONE).max(rect.min);
+    texel(image, pos.x as u32, pos.y as u32)
+}
+
+/// Nearest-neighbour samples a whole image, respecting its sampler's address modes.
+fn sample_wrapped(image: &Image, uv: Vec2) -> Vec4 {
+    let address = |coord: f32, mode: AddressMode| match mode {
+        AddressMode::Repeat => coord.rem_euclid(1.0),
+        _ => coord.clamp(0.0, 1.0),
+    };
+    let uv = Vec2::new(
+        address(uv.x, image.sampler_descriptor.address_mode_u),
+        address(uv.y, image.sampler_descriptor.address_mode_v),
+    );
+    let pos = (uv * image.size()).floor().min(image.size() - Vec2::ONE);
+    texel(image, pos.x as u32, pos.y as u32)
+}
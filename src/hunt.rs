@@ -0,0 +1,227 @@
+//! The dolphin every now and then picking out a nearby fish, chasing it
+//! down, and eating it. Chasing only ever sets the dolphin's
+//! [`thrust`](crate::physics::FaithSim::thrust), so gravity and buoyancy
+//! keep working as usual.
+
+use std::time::Duration;
+
+use bevy::math::DVec2;
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::animation::{FaithAnimations, EAT};
+use crate::clock::SimClock;
+use crate::protocol::Trick;
+use crate::rng::SimRng;
+use crate::settings::Settings;
+use crate::tank::{TankId, Tanks};
+use crate::tricks::Tricks;
+use crate::{Faith, Fish, LoadingState};
+
+/// How far away a fish can be for the dolphin to go after it.
+const HUNT_RADIUS: f64 = 320.0;
+/// Chance per second of starting a hunt once the dolphin's rested.
+const HUNT_CHANCE: f64 = 0.15;
+/// How long the dolphin rests after a hunt, in seconds.
+const REST_TIME: f64 = 8.0;
+/// The dolphin gives up on fishes it can't catch within this many seconds.
+const GIVE_UP_TIME: f64 = 6.0;
+
+/// Speed the dolphin chases at, in the physics' own units.
+const CHASE_SPEED: f64 = 40.0;
+/// How quickly the dolphin tries to match the speed it wants to swim at.
+const STEERING_GAIN: f64 = 2.0;
+/// Caps the dolphin's thrust so that it turns in arcs
+/// instead of changing direction on the spot.
+const MAX_THRUST: f64 = 30.0;
+
+/// How close a fish has to be to get eaten, in sprite pixels.
+const EAT_DISTANCE: f64 = 10.0;
+/// Chance of celebrating a catch by leaping out of the water.
+const LEAP_CHANCE: f64 = 0.4;
+const LEAP_SPEED: f64 = 60.0;
+
+/// What the dolphin's chasing, if anything.
+#[derive(Component, Debug, Default)]
+pub struct Hunter {
+    prey: Option<Entity>,
+    /// When the current hunt started, or when the last one ended.
+    since: Duration,
+}
+
+impl Hunter {
+    pub fn is_chasing(&self) -> bool {
+        self.prey.is_some()
+    }
+
+    fn rest(&mut self, now: Duration) {
+        self.prey = None;
+        self.since = now;
+    }
+
+    /// Whether the dolphin's rested long enough since its last hunt.
+    fn rested(&self, now: Duration) -> bool {
+        !self.is_chasing() && (now - self.since).as_secs_f64() >= REST_TIME
+    }
+
+    /// How the hunt goes on, given where the dolphin
+    /// and its prey are, if the prey's still around.
+    fn pursue(&self, now: Duration, position: DVec2, prey: Option<DVec2>, eat_distance: f64) -> Pursuit {
+        match prey {
+            Some(prey) if (now - self.since).as_secs_f64() < GIVE_UP_TIME => {
+                let offset = prey - position;
+                if offset.length() < eat_distance {
+                    Pursuit::Catch
+                } else {
+                    Pursuit::Chase(offset.normalize_or_zero())
+                }
+            }
+            _ => Pursuit::GiveUp,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Pursuit {
+    /// Close enough to eat the fish.
+    Catch,
+    /// Keep swimming this way after it.
+    Chase(DVec2),
+    /// The fish is gone, or got away for too long.
+    GiveUp,
+}
+
+pub struct HuntPlugin;
+
+impl Plugin for HuntPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(LoadingState::Play)
+                .with_system(hunt.label("hunt").after("perform_tricks").before("update_faith")),
+        )
+        .add_system_set(SystemSet::on_enter(LoadingState::Draining).with_system(stop_hunting));
+    }
+
+    fn name(&self) -> &str {
+        "HuntPlugin"
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn hunt(
+    mut commands: Commands,
+    mut faith_query: Query<(Entity, &mut Faith, &mut Hunter, &Tricks)>,
+    fish_query: Query<(Entity, &Transform, &Visibility), With<Fish>>,
+    animations: Res<FaithAnimations>,
+    tanks: Res<Tanks>,
+    mut rng: ResMut<SimRng>,
+    clock: Res<SimClock>,
+    settings: Res<Settings>,
+) {
+    let now = clock.elapsed();
+    // the dolphin lives in the first tank, so only fishes in there are on the menu
+    let in_reach = |entity: Entity| {
+        fish_query
+            .get(entity)
+            .ok()
+            .filter(|(_, transform, visibility)| {
+                visibility.is_visible && tanks.at(transform.translation.truncate()) == Some(TankId(0))
+            })
+            .map(|(_, transform, _)| transform.translation.truncate().as_dvec2())
+    };
+
+    for (entity, mut faith, mut hunter, tricks) in faith_query.iter_mut() {
+        // tricks take over the dolphin's swimming while they last
+        if tricks.current != Trick::Cruise {
+            if hunter.prey.is_some() {
+                hunter.rest(now);
+            }
+            continue;
+        }
+
+        if let Some(prey) = hunter.prey {
+            let eat_distance = EAT_DISTANCE * settings.sprite_scale as f64;
+            let position = in_reach(prey).filter(|_| !faith.sim.held);
+            match hunter.pursue(now, faith.sim.position, position, eat_distance) {
+                Pursuit::Catch => {
+                    commands.entity(prey).despawn();
+                    animations.play(&mut commands, entity, EAT);
+                    if rng.gen_bool(LEAP_CHANCE) {
+                        faith.sim.velocity.y = faith.sim.velocity.y.max(LEAP_SPEED);
+                    }
+                    faith.sim.thrust = DVec2::ZERO;
+                    hunter.rest(now);
+                }
+                Pursuit::Chase(direction) => {
+                    faith
+                        .sim
+                        .swim_towards(direction * CHASE_SPEED, STEERING_GAIN, MAX_THRUST);
+                }
+                Pursuit::GiveUp => hunter.rest(now),
+            }
+        } else if hunter.rested(now)
+            && !faith.sim.held
+            && rng.gen_bool((HUNT_CHANCE * clock.delta_seconds_f64()).min(1.0))
+        {
+            let position = faith.sim.position;
+            hunter.prey = fish_query
+                .iter()
+                .filter_map(|(fish, ..)| Some((fish, in_reach(fish)?.distance(position))))
+                .filter(|(_, distance)| *distance < HUNT_RADIUS)
+                .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+                .map(|(fish, _)| fish);
+            if hunter.prey.is_some() {
+                hunter.since = now;
+            }
+        }
+    }
+}
+
+/// Lets the dolphin sink along with the draining water.
+fn stop_hunting(mut query: Query<(&mut Faith, &mut Hunter)>, clock: Res<SimClock>) {
+    for (mut faith, mut hunter) in query.iter_mut() {
+        faith.sim.thrust = DVec2::ZERO;
+        hunter.rest(clock.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    #[test]
+    fn gives_up_on_fishes_it_cant_catch() {
+        let hunter = Hunter::default();
+        let prey = Some(DVec2::new(100.0, 0.0));
+        let chasing = hunter.pursue(secs(GIVE_UP_TIME - 0.1), DVec2::ZERO, prey, EAT_DISTANCE);
+        assert_eq!(chasing, Pursuit::Chase(DVec2::X));
+        let too_long = hunter.pursue(secs(GIVE_UP_TIME), DVec2::ZERO, prey, EAT_DISTANCE);
+        assert_eq!(too_long, Pursuit::GiveUp);
+        // or that got away entirely
+        let gone = hunter.pursue(secs(1.0), DVec2::ZERO, None, EAT_DISTANCE);
+        assert_eq!(gone, Pursuit::GiveUp);
+    }
+
+    #[test]
+    fn eats_fishes_within_reach() {
+        let hunter = Hunter::default();
+        let position = DVec2::new(50.0, -20.0);
+        let (near, far) = (position + DVec2::new(6.0, 7.0), position + DVec2::new(0.0, -11.0));
+        let catching = hunter.pursue(secs(1.0), position, Some(near), EAT_DISTANCE);
+        assert_eq!(catching, Pursuit::Catch);
+        let chasing = hunter.pursue(secs(1.0), position, Some(far), EAT_DISTANCE);
+        assert_eq!(chasing, Pursuit::Chase(-DVec2::Y));
+    }
+
+    #[test]
+    fn rests_between_hunts() {
+        let mut hunter = Hunter::default();
+        hunter.rest(secs(10.0));
+        assert!(!hunter.rested(secs(10.0 + REST_TIME - 0.1)));
+        assert!(hunter.rested(secs(10.0 + REST_TIME)));
+    }
+}
@@ -0,0 +1,244 @@
+//! Opt-in mouse interaction with the dolphin, enabled with `--interactive`.
+//! Clicking the dolphin bonks it, while dragging it picks it up and throws it.
+
+use std::collections::VecDeque;
+
+use bevy::math::DVec2;
+use bevy::prelude::*;
+use bevy::sprite::Rect;
+use bevy::window::WindowId;
+use bevy::winit::WinitWindows;
+
+use crate::animation::{FaithAnimations, BONK};
+use crate::settings::Settings;
+use crate::util::{texel, to_affine2};
+use crate::window::WindowSize;
+use crate::{Faith, LoadingState};
+
+/// How hard a bonk pushes the dolphin away from the cursor.
+const BONK_IMPULSE: f64 = 40.0;
+/// How far the cursor has to move before a click becomes a drag.
+const DRAG_THRESHOLD: f32 = 8.0;
+/// How far back cursor positions are kept around to estimate its velocity, in seconds.
+const VELOCITY_WINDOW: f64 = 0.1;
+/// Caps how fast the dolphin can be thrown, in units per second.
+const MAX_THROW_SPEED: f32 = 3000.0;
+
+pub struct InteractionPlugin;
+
+impl Plugin for InteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Cursor>()
+            .add_system_to_stage(CoreStage::PreUpdate, track_cursor)
+            .add_system_set(
+                SystemSet::on_update(LoadingState::Play)
+                    .with_system(hover_faith.label("hover_faith"))
+                    .with_system(grab_faith.after("hover_faith")),
+            );
+    }
+
+    fn name(&self) -> &str {
+        "InteractionPlugin"
+    }
+}
+
+/// Where the cursor is in world coordinates,
+/// and which dolphin it's over or holding, if any.
+#[derive(Debug, Default)]
+pub struct Cursor {
+    pub position: Option<Vec2>,
+    pub hovered_faith: Option<Entity>,
+    pub held_faith: Option<HeldFaith>,
+    pub velocity: CursorVelocity,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HeldFaith {
+    pub entity: Entity,
+    /// Offset from the cursor to the dolphin when it was grabbed.
+    pub offset: Vec2,
+    pub grabbed_at: Vec2,
+    /// Whether this has moved far enough to be a drag rather than a click.
+    pub dragged: bool,
+}
+
+/// Estimates how fast the cursor is moving from its recent positions.
+#[derive(Debug, Default)]
+pub struct CursorVelocity {
+    samples: VecDeque<(f64, Vec2)>,
+}
+
+impl CursorVelocity {
+    pub fn push(&mut self, time: f64, position: Vec2) {
+        self.samples.push_back((time, position));
+        while self.samples.len() > 2 && self.samples[0].0 < time - VELOCITY_WINDOW {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Average velocity over the last [`VELOCITY_WINDOW`], in units per second.
+    pub fn get(&self) -> Vec2 {
+        match (self.samples.front(), self.samples.back()) {
+            (Some(&(start_time, start)), Some(&(end_time, end))) if end_time > start_time => {
+                (end - start) / (end_time - start_time) as f32
+            }
+            _ => Vec2::ZERO,
+        }
+    }
+}
+
+fn track_cursor(
+    windows: Res<Windows>,
+    winit_windows: Res<WinitWindows>,
+    window_size: Res<WindowSize>,
+    time: Res<Time>,
+    mut cursor: ResMut<Cursor>,
+) {
+    let window_size = window_size.0;
+
+    // the window is usually click-through, so winit won't see the cursor
+    // unless this platform doesn't have a way to pass clicks through
+    let position = winit_windows
+        .get_window(WindowId::primary())
+        .and_then(crate::window::cursor_position)
+        .map(|pos| Vec2::new(pos.x - window_size.x / 2.0, window_size.y / 2.0 - pos.y))
+        .or_else(|| {
+            windows
+                .get_primary()
+                .and_then(|window| window.cursor_position())
+                .map(|pos| pos - window_size / 2.0)
+        });
+    update_cursor(&mut cursor, position, &time);
+}
+
+fn update_cursor(cursor: &mut Cursor, position: Option<Vec2>, time: &Time) {
+    cursor.position = position;
+    match position {
+        Some(position) => cursor.velocity.push(time.seconds_since_startup(), position),
+        None => cursor.velocity.clear(),
+    }
+}
+
+fn hover_faith(
+    faith_query: Query<(Entity, &GlobalTransform, &TextureAtlasSprite, &Handle<TextureAtlas>), With<Faith>>,
+    atlases: Res<Assets<TextureAtlas>>,
+    images: Res<Assets<Image>>,
+    winit_windows: Res<WinitWindows>,
+    mut cursor: ResMut<Cursor>,
+    mut catching_clicks: Local<bool>,
+) {
+    let hovered_faith = cursor.position.and_then(|position| {
+        faith_query
+            .iter()
+            .find(|(_, transform, sprite, atlas)| {
+                atlases
+                    .get(*atlas)
+                    .and_then(|atlas| Some((atlas.textures[sprite.index], images.get(&atlas.texture)?)))
+                    .map_or(false, |(rect, image)| {
+                        hit_test(transform, sprite, rect, image, position)
+                    })
+            })
+            .map(|(entity, ..)| entity)
+    });
+
+    cursor.hovered_faith = hovered_faith;
+
+    // only catch clicks while they'd land on the dolphin,
+    // or while it's being dragged so it can be let go of
+    let catch_clicks = cursor.hovered_faith.is_some() || cursor.held_faith.is_some();
+    if catch_clicks != *catching_clicks {
+        *catching_clicks = catch_clicks;
+        crate::window::set_click_through(winit_windows.get_window(WindowId::primary()).unwrap(), !catch_clicks);
+    }
+}
+
+/// Checks whether `point` lands on an opaque pixel
+/// of the sprite's current Aseprite frame.
+fn hit_test(transform: &GlobalTransform, sprite: &TextureAtlasSprite, rect: Rect, image: &Image, point: Vec2) -> bool {
+    let local = to_affine2(transform).inverse().transform_point2(point);
+    let half_size = rect.size() / 2.0;
+    if local.abs().cmpgt(half_size).any() {
+        return false;
+    }
+
+    let x = if sprite.flip_x {
+        half_size.x - local.x
+    } else {
+        local.x + half_size.x
+    };
+    let y = if sprite.flip_y {
+        local.y + half_size.y
+    } else {
+        half_size.y - local.y
+    };
+    let texel_pos = (rect.min + Vec2::new(x, y)).floor().min(rect.max - Vec2::ONE);
+    texel(image, texel_pos.x as u32, texel_pos.y as u32).w > 0.0
+}
+
+fn grab_faith(
+    mut commands: Commands,
+    mut faith_query: Query<&mut Faith>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    mut cursor: ResMut<Cursor>,
+    animations: Res<FaithAnimations>,
+    settings: Res<Settings>,
+) {
+    if let (Some(entity), Some(position)) = (cursor.hovered_faith, cursor.position) {
+        if mouse_buttons.just_pressed(MouseButton::Left) {
+            if let Ok(mut faith) = faith_query.get_mut(entity) {
+                faith.sim.held = true;
+                cursor.held_faith = Some(HeldFaith {
+                    entity,
+                    offset: faith.sim.position.as_vec2() - position,
+                    grabbed_at: position,
+                    dragged: false,
+                });
+            }
+        }
+    }
+
+    let mut held = match cursor.held_faith {
+        Some(held) => held,
+        None => return,
+    };
+    let mut faith = match faith_query.get_mut(held.entity) {
+        Ok(faith) => faith,
+        Err(_) => {
+            cursor.held_faith = None;
+            return;
+        }
+    };
+
+    // let go if the cursor leaves the window too
+    let position = cursor.position.unwrap_or(held.grabbed_at);
+    let released = mouse_buttons.just_released(MouseButton::Left) || cursor.position.is_none();
+    held.dragged |= position.distance(held.grabbed_at) > DRAG_THRESHOLD;
+
+    // the physics runs faster than real time, so scale the cursor's velocity down to match
+    let velocity = cursor.velocity.get().clamp_length_max(MAX_THROW_SPEED).as_dvec2() / settings.speed;
+
+    if released {
+        faith.sim.held = false;
+        cursor.held_faith = None;
+        if held.dragged {
+            faith.sim.velocity = velocity;
+        } else {
+            // push the dolphin away from wherever it was bonked
+            let direction = (faith.sim.position - position.as_dvec2()).normalize_or_zero();
+            let direction = if direction == DVec2::ZERO { DVec2::Y } else { direction };
+            faith.sim.velocity += direction * BONK_IMPULSE;
+
+            animations.play(&mut commands, held.entity, BONK);
+        }
+    } else {
+        if held.dragged {
+            faith.sim.position = (position + held.offset).as_dvec2();
+            faith.sim.velocity = velocity;
+        }
+        cursor.held_faith = Some(held);
+    }
+}
@@ -0,0 +1,201 @@
+//! Unix socket server that lets other programs (like `dolphinctl`)
+//! control the aquarium. Each command that comes in is sent on to
+//! the rest of the app as a [`ControlCommand`](crate::protocol::ControlCommand)
+//! event. The app registers that event itself, so it's there even
+//! when running headless or on platforms without a socket server.
+
+use bevy::prelude::*;
+
+pub struct IpcPlugin;
+
+impl Plugin for IpcPlugin {
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    fn build(&self, app: &mut App) {
+        #[cfg(unix)]
+        {
+            let path = crate::protocol::socket_path();
+            match server::IpcServer::start(path.clone()) {
+                Ok(server) => {
+                    info!("Listening for commands on {}", path.display());
+                    app.insert_resource(server)
+                        .add_system_to_stage(CoreStage::PreUpdate, server::forward_commands)
+                        .add_system_to_stage(CoreStage::Last, server::remove_socket_on_exit);
+                }
+                Err(err) => error!("Couldn't listen for commands on {}: {}", path.display(), err),
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "IpcPlugin"
+    }
+}
+
+#[cfg(unix)]
+mod server {
+    use std::io::{BufRead, BufReader, ErrorKind, Write};
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::{Path, PathBuf};
+
+    use bevy::app::AppExit;
+    use bevy::prelude::*;
+    use crossbeam_channel::{Receiver, Sender};
+
+    use crate::protocol::{ControlCommand, Response};
+
+    pub struct IpcServer {
+        path: PathBuf,
+        commands: Receiver<ControlCommand>,
+    }
+
+    impl IpcServer {
+        pub fn start(path: PathBuf) -> std::io::Result<Self> {
+            if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+                // never delete anything that isn't a socket, in case the path's wrong
+                if !metadata.file_type().is_socket() {
+                    return Err(std::io::Error::new(
+                        ErrorKind::AlreadyExists,
+                        "something other than a socket is already there",
+                    ));
+                }
+                // a socket that nothing's listening on was left behind by a crash
+                if UnixStream::connect(&path).is_ok() {
+                    return Err(std::io::Error::new(
+                        ErrorKind::AddrInUse,
+                        "another aquarium is already listening there",
+                    ));
+                }
+                std::fs::remove_file(&path)?;
+            }
+
+            let listener = UnixListener::bind(&path)?;
+            let (sender, commands) = crossbeam_channel::unbounded();
+            std::thread::Builder::new().name("ipc".to_string()).spawn(move || {
+                for stream in listener.incoming() {
+                    match stream {
+                        Ok(stream) => {
+                            let sender = sender.clone();
+                            std::thread::spawn(move || handle_client(stream, sender));
+                        }
+                        Err(err) => warn!("Couldn't accept IPC connection: {}", err),
+                    }
+                }
+            })?;
+
+            Ok(Self { path, commands })
+        }
+
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+    }
+
+    impl Drop for IpcServer {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    /// Answers every line the client sends until it hangs up.
+    fn handle_client(stream: UnixStream, sender: Sender<ControlCommand>) {
+        let mut writer = match stream.try_clone() {
+            Ok(writer) => writer,
+            Err(err) => {
+                warn!("Couldn't set up IPC connection: {}", err);
+                return;
+            }
+        };
+
+        for line in BufReader::new(stream).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => return,
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str::<ControlCommand>(&line) {
+                Ok(command) => match command.validate() {
+                    Ok(()) => match sender.send(command) {
+                        Ok(()) => Response::ok(),
+                        Err(_) => Response::error("the aquarium is shutting down"),
+                    },
+                    Err(err) => Response::error(err),
+                },
+                Err(err) => Response::error(err.to_string()),
+            };
+
+            let mut response = serde_json::to_string(&response).unwrap();
+            response.push('\n');
+            if writer.write_all(response.as_bytes()).is_err() {
+                return;
+            }
+        }
+    }
+
+    pub fn forward_commands(server: Res<IpcServer>, mut events: EventWriter<ControlCommand>) {
+        for command in server.commands.try_iter() {
+            info!("Received command {:?}", command);
+            events.send(command);
+        }
+    }
+
+    /// The winit event loop exits the process without dropping
+    /// any resources, so clean up the socket by hand.
+    pub fn remove_socket_on_exit(server: Res<IpcServer>, mut exits: EventReader<AppExit>) {
+        if exits.iter().next().is_some() {
+            let _ = std::fs::remove_file(server.path());
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::protocol;
+
+        /// A socket path of its own for each test, since they run in parallel.
+        fn socket_path(test: &str) -> PathBuf {
+            std::env::temp_dir().join(format!("trustworthy-dolphin-{}-{}.sock", test, std::process::id()))
+        }
+
+        #[test]
+        fn round_trips_commands() {
+            let path = socket_path("round-trip");
+            let server = IpcServer::start(path.clone()).unwrap();
+
+            let command = ControlCommand::SpawnFish { count: 3 };
+            protocol::send(&path, &command).unwrap();
+            // the command's passed on before the client hears back
+            assert_eq!(server.commands.try_iter().collect::<Vec<_>>(), vec![command]);
+
+            let invalid = ControlCommand::SpawnFish { count: 0 };
+            assert!(protocol::send(&path, &invalid).is_err());
+            assert!(server.commands.try_recv().is_err());
+        }
+
+        #[test]
+        fn replaces_stale_sockets() {
+            let path = socket_path("stale");
+            drop(UnixListener::bind(&path).unwrap());
+            let server = IpcServer::start(path.clone()).unwrap();
+
+            let other = IpcServer::start(path.clone()).map(|_| ()).unwrap_err();
+            assert_eq!(other.kind(), ErrorKind::AddrInUse);
+            drop(server);
+            assert!(!path.exists());
+        }
+
+        #[test]
+        fn leaves_other_files_alone() {
+            let path = socket_path("file");
+            std::fs::write(&path, "not a socket").unwrap();
+
+            let err = IpcServer::start(path.clone()).map(|_| ()).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a socket");
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
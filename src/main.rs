@@ -12,6 +12,7 @@
 #[cfg(all(not(debug_assertions), feature = "bevy_dyn"))]
 compile_error!("Bevy should not be dynamically linked for release builds!");
 
+use std::f32::consts::TAU;
 use std::f64::consts::PI;
 use std::time::Duration;
 
@@ -20,28 +21,85 @@ use bevy::asset::AssetPlugin;
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
 use bevy::math::DVec2;
 use bevy::prelude::*;
-use bevy::render::mesh::VertexAttributeValues;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
 use bevy::render::render_resource::AddressMode;
+use bevy::sprite::Mesh2dHandle;
 use bevy::tasks::IoTaskPool;
-use bevy::window::WindowId;
-use bevy::winit::WinitWindows;
 use bevy::DefaultPlugins;
 use bevy_ase::asset::{Animation as AseAnimation, AseFileMap};
 use bevy_ase::loader::{AseLoaderDefaultPlugin, Loader as AseLoader};
 use rand::Rng;
 
+use crate::animation::{FaithAnimationPlugin, FaithAnimations, TagAnimation, FAITH_TAGS, SWIM};
 use crate::assets::{include_assets, EmbeddedAssetsPlugin};
+use crate::boids::{Boid, Flock, School, SpatialHash, NEIGHBOR_RADIUS};
+use crate::cli::Options;
+use crate::clock::{start_clock, ClockPlugin, SimClock};
+use crate::drain::DrainPlugin;
+use crate::hunt::{HuntPlugin, Hunter};
+use crate::interaction::InteractionPlugin;
+use crate::ipc::IpcPlugin;
+use crate::pause::PausePlugin;
+use crate::physics::FaithSim;
+use crate::pod::{PodMember, PodPlugin};
+use crate::protocol::ControlCommand;
+use crate::resize::ResizePlugin;
+use crate::rng::{RngPlugin, SimRng};
+use crate::settings::{Settings, SettingsPlugin};
+use crate::speech::SpeechPlugin;
+use crate::splash::{FaithCrossedSurface, SplashPlugin};
+use crate::steering::{Cruise, Depth, Flee, Steering, Wander, FLEE_SPEEDUP};
+use crate::swim::{SwimPlugin, Swimmer};
+use crate::tank::{Tank, TankId, TankPlugin, Tanks};
+use crate::tricks::{TrickPlugin, Tricks};
 use crate::util::Also;
+use crate::waves::WaveSurface;
+use crate::window::WindowSize;
 
+mod animation;
 mod assets;
+mod boids;
+mod cli;
+mod clock;
+mod drain;
+mod headless;
+mod hunt;
+mod interaction;
+mod ipc;
+mod pause;
+mod physics;
+mod pod;
+// shared with `dolphinctl`
+#[cfg_attr(not(unix), allow(dead_code))]
+mod protocol;
+mod resize;
+mod rng;
+mod settings;
+mod speech;
+mod splash;
+mod steering;
+mod swim;
+mod tank;
+mod tricks;
 mod util;
+mod waves;
 mod window;
+#[cfg(target_os = "linux")]
+mod x11;
 
 pub const FAITH_TEXTURE_PATH: &str = "faith.ase";
 pub const WAVE_TEXTURE_PATH: &str = "wave.ase";
 pub const BUBBLE_TEXTURE_PATH: &str = "bubble.ase";
 pub const FISH_TEXTURE_PATH: &str = "fish.ase";
 
+/// How many textures there are in `fish.ase`.
+const FISH_SPECIES: usize = 6;
+/// How close the dolphin can get to each species before they flee.
+const FISH_FLEE_RADII: [f32; FISH_SPECIES] = [140.0, 110.0, 180.0, 90.0, 160.0, 120.0];
+/// How far past the edge they came in from fishes that
+/// turned around to flee can swim before they're gone.
+const FISH_FLEE_MARGIN: f32 = 64.0;
+
 pub const ASSETS: [&str; 4] = [
     FAITH_TEXTURE_PATH,
     WAVE_TEXTURE_PATH,
@@ -49,31 +107,33 @@ pub const ASSETS: [&str; 4] = [
     FISH_TEXTURE_PATH,
 ];
 
-const STANDARD_GRAVITY: f64 = 9.80665;
-const SPEED_MULTIPLER: f64 = 6.0;
-const MAX_STEP_TIME: f64 = 1.0 / 60.0;
-
-const MAX_BUBBLES: u32 = 16;
-const MAX_FISHES: u32 = 32;
+const SPLASH_STRENGTH: f64 = 0.5;
+/// How long the water takes to fill back up after resuming, in seconds.
+const REFILL_TIME: f64 = 0.5;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum LoadingState {
     Loading,
     FillingWater,
     Play,
+    Paused,
+    Draining,
 }
 
 #[derive(Component)]
 struct Water {
     pub start_time: Duration,
     pub water_level: f64,
+    /// Where the water level was when it started draining.
+    pub drain_from: f64,
+    /// Set after resuming, while the water quickly fills back up.
+    pub refilling: bool,
+    pub surface: WaveSurface,
 }
 
 #[derive(Component)]
 struct Faith {
-    pub position: DVec2,
-    pub velocity: DVec2,
-    pub rotation: f64,
+    pub sim: FaithSim,
 }
 
 #[derive(Component)]
@@ -89,10 +149,24 @@ struct Fish {
     pub start_time: Duration,
     pub flip: bool,
     pub speed: f64,
+    /// Which texture in `fish.ase` this fish uses.
+    /// Fishes only school with their own species.
+    pub species: usize,
+    pub velocity: Vec2,
+    /// Keeps fishes from all drifting up and down in lockstep.
+    pub phase: f32,
+    /// How scared the fish is, from 0 to 1. Scared fishes
+    /// swim faster and may turn around to get away.
+    pub fear: f32,
 }
 
 fn main() {
+    let options = Options::from_env();
+    let headless = options.headless.clone();
+    let interactive = options.interactive;
+
     App::new()
+        .insert_resource(options)
         .insert_resource(WindowDescriptor {
             title: "Trustworthy Dolphin!".to_string(),
             resizable: false,
@@ -110,6 +184,9 @@ fn main() {
             if cfg!(feature = "embed_assets") {
                 group.add_before::<AssetPlugin, _>(EmbeddedAssetsPlugin);
             }
+            if let Some(options) = headless.clone() {
+                headless::configure_plugins(group, options);
+            }
             group
         })
         .also(|app| {
@@ -120,22 +197,64 @@ fn main() {
         })
         .add_plugin(AnimationPlugin::default())
         .add_plugin(AseLoaderDefaultPlugin)
-        .add_plugin(window::WindowHandlingPlugin)
+        .add_plugin(ClockPlugin)
+        .add_plugin(TankPlugin)
+        .add_plugin(RngPlugin)
+        .add_plugin(SettingsPlugin)
+        .add_plugin(SpeechPlugin)
+        .add_plugin(DrainPlugin)
+        .add_plugin(PausePlugin)
+        .add_plugin(SplashPlugin)
+        .add_plugin(FaithAnimationPlugin)
+        .add_plugin(HuntPlugin)
+        .add_plugin(TrickPlugin)
+        .add_plugin(PodPlugin)
+        .add_plugin(SwimPlugin)
+        .also(|app| {
+            if headless.is_none() {
+                app.add_plugin(IpcPlugin)
+                    .add_plugin(window::WindowHandlingPlugin)
+                    .add_plugin(ResizePlugin);
+                if interactive {
+                    app.add_plugin(InteractionPlugin);
+                }
+            }
+        })
+        // headless runs have no control socket, but still read its commands
+        .add_event::<ControlCommand>()
         .add_state(LoadingState::Loading)
         .add_system_set(SystemSet::on_enter(LoadingState::Loading).with_system(load_assets))
         .add_system_set(SystemSet::on_update(LoadingState::Loading).with_system(check_loading))
+        // filling the water takes a fixed amount of simulated time, unlike loading
+        .add_system_set(SystemSet::on_exit(LoadingState::Loading).with_system(start_clock))
         .add_system_set(
             SystemSet::on_enter(LoadingState::FillingWater)
-                .with_system(setup_camera)
+                .with_system(tank::setup_cameras)
                 .with_system(setup_water),
         )
         .add_system_set(SystemSet::on_update(LoadingState::FillingWater).with_system(fill_water))
         .add_system_set(SystemSet::on_enter(LoadingState::Play).with_system(spawn_faith))
         .add_system_set(
             SystemSet::on_update(LoadingState::Play)
-                .with_system(wave_water.chain(update_faith))
-                .with_system(update_bubbles)
-                .with_system(update_fishes),
+                .with_system(wave_water.label("water_level"))
+                .with_system(animate_water.after("water_level"))
+                .with_system(ripple_water)
+                // everything drawing from the shared rng runs in a fixed
+                // order, so that the same seed plays out the same way
+                .with_system(update_bubbles.label("bubbles"))
+                .with_system(update_fishes.label("fishes").after("bubbles"))
+                .with_system(spawn_requested_fishes.label("spawn_fishes").after("fishes"))
+                .with_system(
+                    update_faith
+                        .label("update_faith")
+                        .after("water_level")
+                        .after("spawn_fishes"),
+                ),
+        )
+        .add_system_set(
+            SystemSet::on_update(LoadingState::Draining)
+                .with_system(animate_water.after("water_level"))
+                .with_system(update_faith.after("water_level")),
         )
         .run();
 }
@@ -152,99 +271,165 @@ fn check_loading(mut state: ResMut<State<LoadingState>>, ase_loader: Res<AseLoad
     }
 }
 
-fn setup_camera(mut commands: Commands) {
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
-}
-
-fn get_primary_window_size(windows: &Res<WinitWindows>) -> Vec2 {
-    let primary_window = windows.get_window(WindowId::primary()).unwrap();
-    let logical_size = primary_window
-        .inner_size()
-        .to_logical::<f32>(primary_window.scale_factor());
-    Vec2::new(logical_size.width, logical_size.height)
-}
-
 fn setup_water(
     mut commands: Commands,
-    windows: Res<WinitWindows>,
+    tanks: Res<Tanks>,
     ase_assets: Res<AseFileMap>,
     mut images: ResMut<Assets<Image>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
-    time: Res<Time>,
+    clock: Res<SimClock>,
 ) {
-    let window_size = get_primary_window_size(&windows);
-
     let wave_ase = ase_assets.get(WAVE_TEXTURE_PATH.as_ref()).unwrap();
     let wave_texture_handle = wave_ase.texture(0).unwrap();
     let wave_texture = images.get_mut(wave_texture_handle).unwrap();
     wave_texture.sampler_descriptor.address_mode_u = AddressMode::Repeat;
     wave_texture.sampler_descriptor.address_mode_v = AddressMode::ClampToEdge;
+    let material = materials.add(ColorMaterial::from(wave_texture_handle.clone()));
 
-    let wave_mesh = {
-        let mut mesh = Mesh::from(shape::Quad::new(window_size));
-        if let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute_mut(Mesh::ATTRIBUTE_UV_0) {
-            //  0  1  2  3
-            // SW NW NE SE
-            const UV_SCALE: f32 = 1.0 / 128.0;
-            uvs[0][1] = window_size.y * UV_SCALE;
-            uvs[2][0] = window_size.x * UV_SCALE;
-            uvs[3] = [window_size.x * UV_SCALE, window_size.y * UV_SCALE];
-        } else {
-            panic!("Mesh doesn't have UV coordinates?");
-        }
-        mesh
-    };
+    for id in tanks.ids() {
+        let tank = tanks.get(id);
+        let surface = WaveSurface::new(tank.size.x as f64);
+        let wave_mesh = wave_mesh(tank.size, &surface);
 
-    commands
-        .spawn_bundle(ColorMesh2dBundle {
-            mesh: meshes.add(wave_mesh).into(),
-            material: materials.add(ColorMaterial::from(wave_texture_handle.clone())),
-            transform: Transform {
-                translation: Vec3::new(0.0, -window_size.y, 1.0),
-                ..Transform::default()
-            },
-            ..ColorMesh2dBundle::default()
-        })
-        .insert(Water {
-            start_time: time.time_since_startup(),
-            water_level: 0.0,
-        });
+        commands
+            .spawn_bundle(ColorMesh2dBundle {
+                mesh: meshes.add(wave_mesh).into(),
+                material: material.clone(),
+                transform: Transform {
+                    translation: Vec3::new(tank.center.x, tank.center.y - tank.size.y, 1.0),
+                    ..Transform::default()
+                },
+                ..ColorMesh2dBundle::default()
+            })
+            .insert(Water {
+                start_time: clock.elapsed(),
+                water_level: 0.0,
+                drain_from: 0.0,
+                refilling: false,
+                surface,
+            })
+            .insert(id);
+    }
+}
+
+/// Builds the water mesh as a strip of quads,
+/// one per column of the wave surface.
+fn wave_mesh(window_size: Vec2, surface: &WaveSurface) -> Mesh {
+    let columns = surface.heights().len();
+
+    // column `i` has its top vertex at `2i` and its bottom vertex at `2i + 1`
+    let mut indices = Vec::with_capacity((columns - 1) * 6);
+    for i in 0..columns as u32 - 1 {
+        let (top, bottom, next_top, next_bottom) = (2 * i, 2 * i + 1, 2 * i + 2, 2 * i + 3);
+        indices.extend_from_slice(&[bottom, next_bottom, next_top, bottom, next_top, top]);
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; columns * 2]);
+    apply_wave_surface(&mut mesh, window_size, surface);
+    mesh
+}
+
+/// Moves the top edge of the water mesh to follow the wave surface.
+fn apply_wave_surface(mesh: &mut Mesh, window_size: Vec2, surface: &WaveSurface) {
+    const UV_SCALE: f32 = 1.0 / 128.0;
+
+    let columns = surface.heights().len();
+    let mut positions = Vec::with_capacity(columns * 2);
+    let mut uvs = Vec::with_capacity(columns * 2);
+    for (i, &height) in surface.heights().iter().enumerate() {
+        let x = (surface.column_x(i) as f32).min(window_size.x / 2.0);
+        let top = window_size.y / 2.0 + height as f32;
+        let bottom = -window_size.y / 2.0;
+        let u = (x + window_size.x / 2.0) * UV_SCALE;
+
+        // keep the wave texture stuck to the surface
+        positions.push([x, top, 0.0]);
+        uvs.push([u, 0.0]);
+        positions.push([x, bottom, 0.0]);
+        uvs.push([u, (top - bottom) * UV_SCALE]);
+    }
+
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
 }
 
 fn fill_water(
-    mut query: Query<(&mut Water, &mut Transform)>,
+    mut query: Query<(&mut Water, &mut Transform, &TankId)>,
     mut state: ResMut<State<LoadingState>>,
-    windows: Res<WinitWindows>,
-    time: Res<Time>,
+    tanks: Res<Tanks>,
+    clock: Res<SimClock>,
 ) {
-    let (mut water, transform): (Mut<Water>, Mut<Transform>) = query.single_mut();
-    let anim_time = (time.time_since_startup() - water.start_time).as_secs_f64();
+    let mut filled = false;
+    for component in query.iter_mut() {
+        let (mut water, transform, tank): (Mut<Water>, Mut<Transform>, &TankId) = component;
+        let anim_time = (clock.elapsed() - water.start_time).as_secs_f64();
+
+        if anim_time >= 1.0 {
+            water.water_level = 1.0;
+            water.start_time = clock.elapsed();
+            filled = true;
+        } else {
+            water.water_level = fill_curve(anim_time);
+        }
 
-    if anim_time >= 1.0 {
-        water.water_level = 1.0;
-        water.start_time = time.time_since_startup();
+        update_water_transform(water, transform, tanks.get(*tank));
+    }
+
+    // every tank started filling at the same time
+    if filled {
         state.set(LoadingState::Play).unwrap();
-    } else {
-        water.water_level = -16.0 * (anim_time - 1.0).powf(4.0) + 1.0;
     }
+}
 
-    update_water_transform(water, transform, windows);
+/// Quartic ease-out that takes the water level from empty
+/// to full as `anim_time` goes from 0.5 to 1.
+fn fill_curve(anim_time: f64) -> f64 {
+    -16.0 * (anim_time - 1.0).powf(4.0) + 1.0
 }
 
-fn wave_water(mut query: Query<(&mut Water, &mut Transform)>, windows: Res<WinitWindows>, time: Res<Time>) {
-    let (mut water, transform): (Mut<Water>, Mut<Transform>) = query.single_mut();
-    let anim_time = time.time_since_startup() - water.start_time;
-    let wave_time = Duration::new(anim_time.as_secs() % 10, anim_time.subsec_nanos()).as_secs_f64();
-    let wave_y = f64::sin(0.4 * PI * wave_time) + f64::sin(0.6 * PI * wave_time);
-    water.water_level = 1.0 + 0.01 * wave_y;
+fn wave_water(mut query: Query<&mut Water>, clock: Res<SimClock>) {
+    for mut water in query.iter_mut() {
+        let anim_time = clock.elapsed() - water.start_time;
+        let wave_time = Duration::new(anim_time.as_secs() % 10, anim_time.subsec_nanos()).as_secs_f64();
+        let wave_y = f64::sin(0.4 * PI * wave_time) + f64::sin(0.6 * PI * wave_time);
+        water.water_level = 1.0 + 0.01 * wave_y;
+
+        if water.refilling {
+            // the second half of the fill curve goes from empty to full
+            let fill_time = anim_time.as_secs_f64() / REFILL_TIME;
+            if fill_time < 1.0 {
+                water.water_level = water.water_level.min(fill_curve(0.5 + fill_time / 2.0));
+            } else {
+                water.refilling = false;
+            }
+        }
+    }
+}
 
-    update_water_transform(water, transform, windows);
+/// Moves the water to its current level and ripples its surface.
+fn animate_water(
+    mut query: Query<(&mut Water, &mut Transform, &Mesh2dHandle, &TankId)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    tanks: Res<Tanks>,
+    clock: Res<SimClock>,
+) {
+    for component in query.iter_mut() {
+        let (mut water, transform, mesh, tank): (Mut<Water>, Mut<Transform>, &Mesh2dHandle, &TankId) = component;
+        let tank = tanks.get(*tank);
+        water.surface.step(clock.delta_seconds_f64());
+        if let Some(mesh) = meshes.get_mut(&mesh.0) {
+            apply_wave_surface(mesh, tank.size, &water.surface);
+        }
+
+        update_water_transform(water, transform, tank);
+    }
 }
 
-fn update_water_transform(water: Mut<Water>, mut transform: Mut<Transform>, windows: Res<WinitWindows>) {
-    let window_size = get_primary_window_size(&windows);
-    transform.translation.y = ((-1.0 + water.water_level * 0.5) * window_size.y as f64) as f32;
+fn update_water_transform(water: Mut<Water>, mut transform: Mut<Transform>, tank: &Tank) {
+    transform.translation.y = tank.center.y + ((-1.0 + water.water_level * 0.5) * tank.size.y as f64) as f32;
 }
 
 fn spawn_faith(
@@ -252,88 +437,129 @@ fn spawn_faith(
     ase_assets: Res<AseFileMap>,
     ase_animations: Res<Assets<AseAnimation>>,
     mut animations: ResMut<Assets<SpriteSheetAnimation>>,
-    windows: Res<WinitWindows>,
+    mut faith_animations: ResMut<FaithAnimations>,
+    window_size: Res<WindowSize>,
+    settings: Res<Settings>,
 ) {
-    let window_size = get_primary_window_size(&windows);
+    let window_size = window_size.0;
 
     let faith_ase = ase_assets.get(FAITH_TEXTURE_PATH.as_ref()).unwrap();
-    let swim_animation = ase_animations
-        .get(faith_ase.animations("swim").unwrap().first().unwrap())
-        .unwrap();
-    let animation_handle = animations.add(swim_animation.into());
+    for (tag, looping) in FAITH_TAGS {
+        let ase_animation = faith_ase
+            .animations(tag)
+            .and_then(|animations| animations.first())
+            .and_then(|animation| ase_animations.get(animation));
+        if let Some(ase_animation) = ase_animation {
+            let animation: SpriteSheetAnimation = ase_animation.into();
+            faith_animations.0.insert(
+                tag,
+                TagAnimation {
+                    animation: animations.add(if looping { animation } else { animation.once() }),
+                    atlas: ase_animation.atlas(),
+                    looping,
+                },
+            );
+        } else {
+            warn!(
+                "{} has no `{}` tag, so that animation will be skipped",
+                FAITH_TEXTURE_PATH, tag
+            );
+        }
+    }
+    let swim = faith_animations
+        .get(SWIM)
+        .expect("Faith has no swim animation?")
+        .clone();
 
-    commands
-        .spawn_bundle(SpriteSheetBundle {
-            texture_atlas: swim_animation.atlas(),
-            transform: Transform {
-                scale: Vec2::splat(4.0).extend(0.0),
-                ..Transform::default()
-            },
-            ..SpriteSheetBundle::default()
-        })
-        .insert(animation_handle)
-        .insert(benimator::Play)
-        .insert(Faith {
-            position: DVec2::new(0.0, window_size.y as f64 * 0.5),
-            velocity: DVec2::default(),
-            rotation: 0.0,
-        });
+    for slot in 0..settings.pod_size as usize {
+        let member = PodMember { slot };
+        commands
+            .spawn_bundle(SpriteSheetBundle {
+                sprite: TextureAtlasSprite {
+                    color: member.color(),
+                    ..TextureAtlasSprite::default()
+                },
+                texture_atlas: swim.atlas.clone(),
+                transform: Transform {
+                    scale: Vec2::splat(settings.sprite_scale).extend(0.0),
+                    ..Transform::default()
+                },
+                ..SpriteSheetBundle::default()
+            })
+            .insert(swim.animation.clone())
+            .insert(benimator::Play)
+            .insert(Faith {
+                sim: FaithSim::new(DVec2::new(member.offset(), window_size.y as f64 * 0.5)),
+            })
+            .insert(member)
+            .insert(Swimmer::new(slot as f64))
+            .insert(Hunter::default())
+            .insert(Tricks::default());
+    }
 }
 
 fn update_faith(
     mut faith_query: Query<(&mut Faith, &mut Transform)>,
-    water_query: Query<&Water>,
-    time: Res<Time>,
-    windows: Res<WinitWindows>,
+    water_query: Query<(&Water, &TankId)>,
+    mut crossed_surface: EventWriter<FaithCrossedSurface>,
+    clock: Res<SimClock>,
+    window_size: Res<WindowSize>,
+    settings: Res<Settings>,
 ) {
-    let (mut faith, mut faith_transform): (Mut<Faith>, Mut<Transform>) = faith_query.single_mut();
-
-    let window_size = get_primary_window_size(&windows);
-    let water_level = (-0.5 + water_query.single().water_level * 0.5) * window_size.y as f64;
-
-    // subdivide simulation time to guard against lag spikes
-    let full_delta = time.delta_seconds_f64() * SPEED_MULTIPLER;
-    let steps = (full_delta / MAX_STEP_TIME).ceil() as u64;
-    for i in 0..steps {
-        let delta = if i == steps - 1 {
-            full_delta % MAX_STEP_TIME
-        } else {
-            MAX_STEP_TIME
-        };
-
-        // Update second order displacement
-        if faith.position.y > water_level {
-            faith.velocity.y -= STANDARD_GRAVITY * delta;
-        } else {
-            faith.velocity.y += ((water_level - faith.position.y).sqrt()) * delta;
+    let water = match water_query.iter().find(|(_, tank)| tank.0 == 0) {
+        Some((water, _)) => water,
+        None => return,
+    };
+    let window_size = window_size.0;
+
+    for component in faith_query.iter_mut() {
+        let (mut faith, mut faith_transform): (Mut<Faith>, Mut<Transform>) = component;
+        let water_level = (-0.5 + water.water_level * 0.5) * window_size.y as f64;
+        let water_level = water_level + water.surface.height_at(faith.sim.position.x);
+
+        let was_submerged = faith.sim.position.y <= water_level;
+        faith.sim.step(
+            clock.delta_seconds_f64() * settings.speed,
+            water_level,
+            window_size.as_dvec2(),
+            &settings.sim_params(),
+        );
+
+        if was_submerged != (faith.sim.position.y <= water_level) {
+            crossed_surface.send(FaithCrossedSurface {
+                position: DVec2::new(faith.sim.position.x, water_level),
+                velocity: faith.sim.velocity * settings.speed,
+            });
         }
 
-        // Update first order displacement
-        let displacement = faith.velocity * delta;
-        faith.position += displacement;
-
-        // Update rotation
-        let dist_from_water = 2.0 * (faith.position.y - water_level).abs() / window_size.y as f64;
-        let dir = faith.velocity.y * ((1.0 - dist_from_water).powf(2.0) - 0.01 * dist_from_water);
-        faith.rotation = if faith.position.y > water_level {
-            f64::atan(dir)
-        } else {
-            -PI - f64::atan(dir)
-        };
+        faith_transform.translation = faith.sim.position.as_vec2().extend(0.0);
+        faith_transform.rotation = Quat::from_rotation_z(faith.sim.rotation as f32);
+    }
+}
 
-        // Update transform
-        faith_transform.translation = faith.position.as_vec2().extend(0.0);
-        faith_transform.rotation = Quat::from_rotation_z(faith.rotation as f32);
+fn ripple_water(mut water_query: Query<(&mut Water, &TankId)>, mut crossed_surface: EventReader<FaithCrossedSurface>) {
+    // the dolphin only ever splashes around in the first tank
+    let mut water = match water_query.iter_mut().find(|(_, tank)| tank.0 == 0) {
+        Some((water, _)) => water,
+        None => return,
+    };
+    for event in crossed_surface.iter() {
+        water
+            .surface
+            .splash(event.position.x, event.velocity.y * SPLASH_STRENGTH);
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn update_bubbles(
     mut commands: Commands,
-    mut bubbles_query: Query<(Entity, &mut Bubbles, &Children)>,
+    mut bubbles_query: Query<(Entity, &mut Bubbles, &Children, &TankId)>,
     ase_assets: Res<AseFileMap>,
     images: Res<Assets<Image>>,
-    time: Res<Time>,
-    windows: Res<WinitWindows>,
+    mut rng: ResMut<SimRng>,
+    clock: Res<SimClock>,
+    tanks: Res<Tanks>,
+    settings: Res<Settings>,
 ) {
     let bubble_texture = ase_assets
         .get(BUBBLE_TEXTURE_PATH.as_ref())
@@ -341,16 +567,16 @@ fn update_bubbles(
         .texture(0)
         .unwrap();
 
-    let mut num_bubbles = 0;
+    let mut num_bubbles = vec![0; tanks.tanks.len()];
     for component in bubbles_query.iter_mut() {
-        num_bubbles += 1;
+        let (entity, mut bubbles, children, tank): (Entity, Mut<Bubbles>, &Children, &TankId) = component;
+        num_bubbles[tank.0] += 1;
 
-        let (entity, mut bubbles, children): (Entity, Mut<Bubbles>, &Children) = component;
-        if time.time_since_startup() < bubbles.start_time {
+        if clock.elapsed() < bubbles.start_time {
             // these bubbles aren't supposed to show yet
             continue;
         } else {
-            let time_since_start = (time.time_since_startup() - bubbles.start_time).as_secs_f64();
+            let time_since_start = (clock.elapsed() - bubbles.start_time).as_secs_f64();
             let steps_since_start = (time_since_start / bubbles.delta_between_bubbles).floor() as usize;
 
             if steps_since_start >= bubbles.height * 2 {
@@ -385,96 +611,247 @@ fn update_bubbles(
     }
 
     // make new bubbles
-    if num_bubbles < MAX_BUBBLES {
-        let window_size = get_primary_window_size(&windows);
-        let mut rng = rand::thread_rng();
-
+    for id in tanks.ids().filter(|id| num_bubbles[id.0] < settings.max_bubbles) {
+        let tank = tanks.get(id);
         commands
             .spawn()
             .insert(Bubbles {
-                start_time: time.time_since_startup() + Duration::from_secs_f64(rng.gen_range(0.0..=2.0)),
+                start_time: clock.elapsed() + Duration::from_secs_f64(rng.gen_range(0.0..=2.0)),
                 delta_between_bubbles: rng.gen_range(0.1..=0.4),
-                height: rng.gen_range(4..=16),
+                height: rng.gen_range(settings.bubble_height()),
                 step: 0,
             })
+            .insert(id)
             .insert_bundle(TransformBundle::from_transform(Transform {
                 translation: Vec3::new(
-                    rng.gen_range::<i8, _>(-15..=15) as f32 / 16.0 * window_size.x / 2.0,
-                    -window_size.y / 2.0 + 6.0,
+                    tank.center.x + rng.gen_range::<i8, _>(-15..=15) as f32 / 16.0 * tank.size.x / 2.0,
+                    tank.center.y - tank.size.y / 2.0 + 6.0,
                     -0.0,
                 ),
-                scale: Vec2::splat(2.0).extend(0.0),
+                scale: Vec2::splat(settings.sprite_scale / 2.0).extend(0.0),
                 ..Transform::default()
             }))
             .insert(Children::default());
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn update_fishes(
     mut commands: Commands,
-    mut fish_query: Query<(Entity, &Fish, &mut Visibility, &mut Transform)>,
+    mut fish_query: Query<(Entity, &mut Fish, &TankId, &mut Sprite, &mut Visibility, &mut Transform)>,
+    water_query: Query<(&Water, &TankId)>,
+    faith_query: Query<&Faith>,
     ase_assets: Res<AseFileMap>,
     images: Res<Assets<Image>>,
-    time: Res<Time>,
-    windows: Res<WinitWindows>,
+    mut rng: ResMut<SimRng>,
+    clock: Res<SimClock>,
+    tanks: Res<Tanks>,
+    settings: Res<Settings>,
 ) {
-    let window_size = get_primary_window_size(&windows);
+    let delta = clock.delta_seconds_f64() as f32;
+    let time = clock.elapsed().as_secs_f32();
 
-    let mut num_fishes = 0;
+    let mut surfaces = vec![f32::INFINITY; tanks.tanks.len()];
+    for (water, tank) in water_query.iter() {
+        let Tank { center, size, .. } = *tanks.get(*tank);
+        surfaces[tank.0] = center.y + ((-0.5 + water.water_level * 0.5) * size.y as f64) as f32;
+    }
+    // the dolphin lives in the first tank, which is centered on the origin
+    let threats = faith_query
+        .iter()
+        .map(|faith| faith.sim.position.as_vec2())
+        .collect::<Vec<_>>();
+
+    let mut num_fishes = vec![0; tanks.tanks.len()];
+    let mut entities = Vec::new();
+    let mut fishes = Vec::new();
+    let mut spatial_hash = SpatialHash::new(NEIGHBOR_RADIUS);
     for component in fish_query.iter_mut() {
-        num_fishes += 1;
-
-        let (entity, fish, mut visibility, mut transform): (Entity, &Fish, Mut<Visibility>, Mut<Transform>) = component;
-        if time.time_since_startup() < fish.start_time {
+        let (entity, fish, tank, _, mut visibility, transform): (
+            Entity,
+            Mut<Fish>,
+            &TankId,
+            Mut<Sprite>,
+            Mut<Visibility>,
+            Mut<Transform>,
+        ) = component;
+        num_fishes[tank.0] += 1;
+
+        if clock.elapsed() < fish.start_time {
             // this fish doesn't exist yet
             continue;
-        } else {
-            visibility.is_visible = true;
-            let time_since_start = (time.time_since_startup() - fish.start_time).as_secs_f64();
-            let direction = if fish.flip { -1.0 } else { 1.0 };
-            transform.translation.x = (time_since_start * fish.speed) as f32 + -direction * (window_size.x / 2.0);
-
-            if !fish.flip && transform.translation.x > window_size.x / 2.0
-                || fish.flip && transform.translation.x < -window_size.x / 2.0
-            {
-                commands.entity(entity).despawn();
-            }
         }
+        visibility.is_visible = true;
+        let boid = Boid {
+            position: transform.translation.truncate(),
+            velocity: fish.velocity,
+        };
+        let school = School {
+            species: fish.species,
+            flip: fish.flip,
+        };
+        spatial_hash.insert(fishes.len(), boid.position);
+        entities.push(entity);
+        fishes.push((boid, school));
     }
 
-    // make new fishes
-    if num_fishes < MAX_FISHES {
-        let mut rng = rand::thread_rng();
-
-        let fish_ase = ase_assets.get(FISH_TEXTURE_PATH.as_ref()).unwrap();
-        let fish_texture = fish_ase.texture(rng.gen_range(0..=5)).unwrap();
+    for (index, entity) in entities.iter().enumerate() {
+        let boid = &fishes[index].0;
+        let neighbors = boids::schoolmates(&fishes, &spatial_hash, index);
 
-        let flip = rng.gen::<bool>();
-        let direction = if flip { -1.0 } else { 1.0 };
+        let (_, mut fish, tank, mut sprite, _, mut transform) = fish_query.get_mut(*entity).unwrap();
+        // when spanning, fishes swim under whichever tank they're in
+        let swimming_in = tanks.at(boid.position).unwrap_or(*tank);
+        let Tank { center, size, .. } = *tanks.get(swimming_in);
 
-        commands
-            .spawn_bundle(SpriteBundle {
-                sprite: Sprite {
-                    flip_x: flip,
-                    ..Sprite::default()
-                },
-                transform: Transform {
-                    translation: Vec3::new(
-                        -direction as f32 * window_size.x / 2.0,
-                        rng.gen_range(0.1..=0.5) * -window_size.y,
-                        0.0,
-                    ),
-                    scale: Vec2::splat(4.0).extend(0.0),
-                    ..Transform::default()
-                },
-                texture: images.get_handle(fish_texture),
-                visibility: Visibility { is_visible: false },
-                ..SpriteBundle::default()
+        let flee = threats
+            .iter()
+            .map(|threat| Flee {
+                threat: *threat,
+                radius: FISH_FLEE_RADII[fish.species],
             })
-            .insert(Fish {
-                start_time: time.time_since_startup() + Duration::from_secs_f64(rng.gen_range(1.0..=5.0)),
-                flip,
-                speed: direction * rng.gen_range(20.0..=100.0),
-            });
+            .collect::<Vec<_>>();
+        fish.fear = flee
+            .iter()
+            .map(|flee| flee.fear(boid))
+            .fold(steering::calm_down(fish.fear, delta), f32::max);
+        let cruise_velocity = Vec2::new(fish.speed as f32, 0.0) * (1.0 + fish.fear * FLEE_SPEEDUP);
+
+        let flock = Flock {
+            neighbors: neighbors.collect(),
+        };
+        let cruise = Cruise {
+            velocity: cruise_velocity,
+        };
+        let depth = Depth {
+            surface: surfaces[swimming_in.0],
+            bottom: center.y - size.y / 2.0,
+        };
+        let wander = Wander {
+            time,
+            phase: fish.phase,
+        };
+        let mut behaviors: Vec<&dyn Steering> = vec![&flock, &cruise, &depth, &wander];
+        behaviors.extend(flee.iter().map(|flee| flee as &dyn Steering));
+
+        let acceleration = steering::total_force(boid, &behaviors);
+        // fishes that turned around to flee swing back gradually once they've calmed down
+        let can_turn = fish.fear > 0.0 || boid.velocity.x * cruise_velocity.x < 0.0;
+        fish.velocity = boids::limit_velocity(boid.velocity + acceleration * delta, cruise_velocity, can_turn);
+        transform.translation += (fish.velocity * delta).extend(0.0);
+        // face and tilt along the fish's heading, which can
+        // point backwards for a while when it's fleeing
+        let heading = fish.velocity.x.signum();
+        sprite.flip_x = heading < 0.0;
+        transform.rotation = Quat::from_rotation_z(fish.velocity.y.atan2(fish.velocity.x.abs()) * heading);
+
+        let (left, right) = tanks.lane(*tank);
+        let x = transform.translation.x;
+        let (exit, entrance) = if fish.flip { (left, right) } else { (right, left) };
+        let direction = fish.speed.signum() as f32;
+        let gone = (x - exit) * direction > 0.0 || (entrance - x) * direction > FISH_FLEE_MARGIN;
+        if gone {
+            commands.entity(*entity).despawn();
+        }
     }
+
+    // make new fishes
+    for id in tanks.ids().filter(|id| num_fishes[id.0] < settings.max_fishes) {
+        let start_time = clock.elapsed() + Duration::from_secs_f64(rng.gen_range(1.0..=5.0));
+        spawn_fish(
+            &mut commands,
+            &ase_assets,
+            &images,
+            &mut rng,
+            &settings,
+            &tanks,
+            id,
+            start_time,
+        );
+    }
+}
+
+/// Spawns fishes on top of the usual ones whenever they're asked for.
+#[allow(clippy::too_many_arguments)]
+fn spawn_requested_fishes(
+    mut commands: Commands,
+    mut control: EventReader<ControlCommand>,
+    ase_assets: Res<AseFileMap>,
+    images: Res<Assets<Image>>,
+    mut rng: ResMut<SimRng>,
+    clock: Res<SimClock>,
+    tanks: Res<Tanks>,
+    settings: Res<Settings>,
+) {
+    for command in control.iter() {
+        if let ControlCommand::SpawnFish { count } = command {
+            for _ in 0..*count {
+                let tank = TankId(rng.gen_range(0..tanks.tanks.len()));
+                spawn_fish(
+                    &mut commands,
+                    &ase_assets,
+                    &images,
+                    &mut rng,
+                    &settings,
+                    &tanks,
+                    tank,
+                    clock.elapsed(),
+                );
+            }
+        }
+    }
+}
+
+/// Spawns a fish of a random species that starts swimming
+/// across `tank` (or every tank, when spanning) at `start_time`.
+#[allow(clippy::too_many_arguments)]
+fn spawn_fish(
+    commands: &mut Commands,
+    ase_assets: &AseFileMap,
+    images: &Assets<Image>,
+    rng: &mut SimRng,
+    settings: &Settings,
+    tanks: &Tanks,
+    tank: TankId,
+    start_time: Duration,
+) {
+    let fish_ase = ase_assets.get(FISH_TEXTURE_PATH.as_ref()).unwrap();
+    let species = rng.gen_range(0..FISH_SPECIES);
+    let fish_texture = fish_ase.texture(species).unwrap();
+
+    let flip = rng.gen::<bool>();
+    let direction = if flip { -1.0 } else { 1.0 };
+    let speed = direction * rng.gen_range(settings.fish_speed());
+    let (left, right) = tanks.lane(tank);
+    let Tank { center, size, .. } = *tanks.get(tank);
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                flip_x: flip,
+                ..Sprite::default()
+            },
+            transform: Transform {
+                translation: Vec3::new(
+                    if flip { right } else { left },
+                    center.y + rng.gen_range(0.1..=0.5) * -size.y,
+                    0.0,
+                ),
+                scale: Vec2::splat(settings.sprite_scale).extend(0.0),
+                ..Transform::default()
+            },
+            texture: images.get_handle(fish_texture),
+            visibility: Visibility { is_visible: false },
+            ..SpriteBundle::default()
+        })
+        .insert(Fish {
+            start_time,
+            flip,
+            speed,
+            species,
+            velocity: Vec2::new(speed as f32, 0.0),
+            phase: rng.gen_range(0.0..TAU),
+            fear: 0.0,
+        })
+        .insert(tank);
 }
@@ -0,0 +1,152 @@
+//! Hiding the aquarium without quitting, for meetings and presentations,
+//! and automatically while a fullscreen app is focused.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::winit::WinitWindows;
+
+use crate::cli::Options;
+use crate::clock::SimClock;
+use crate::protocol::ControlCommand;
+use crate::settings::Settings;
+use crate::{LoadingState, Water};
+
+/// Bevy can't stop redrawing the window, so while paused
+/// just run the app loop this often instead.
+const PAUSED_FRAME_TIME: Duration = Duration::from_millis(250);
+/// How often to check for fullscreen apps, in seconds.
+const FULLSCREEN_CHECK_INTERVAL: f64 = 0.5;
+
+/// Keeps pauses for fullscreen apps from undoing
+/// or fighting with the ones asked for by the user.
+#[derive(Debug, Default)]
+struct FullscreenPause {
+    /// Paused for a fullscreen app, so resume once it's gone.
+    active: bool,
+    /// Resumed by hand while the fullscreen app was still focused.
+    overridden: bool,
+}
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FullscreenPause>()
+            .add_system_to_stage(CoreStage::PreUpdate, toggle_pause.label("toggle_pause"))
+            .add_system_to_stage(CoreStage::PreUpdate, hide_for_fullscreen.after("toggle_pause"))
+            .add_system_set(SystemSet::on_enter(LoadingState::Paused).with_system(pause))
+            .add_system_set(SystemSet::on_update(LoadingState::Paused).with_system(throttle))
+            .add_system_set(SystemSet::on_exit(LoadingState::Paused).with_system(resume))
+            .add_system_set(SystemSet::on_resume(LoadingState::Play).with_system(refill_water));
+    }
+
+    fn name(&self) -> &str {
+        "PausePlugin"
+    }
+}
+
+fn toggle_pause(
+    mut control: EventReader<ControlCommand>,
+    mut state: ResMut<State<LoadingState>>,
+    mut fullscreen_pause: ResMut<FullscreenPause>,
+) {
+    for command in control.iter() {
+        // the paused state is pushed on top of whatever was running
+        // so that it can be picked up again exactly where it left off
+        match (command, state.current()) {
+            (ControlCommand::Pause, LoadingState::Loading | LoadingState::FillingWater | LoadingState::Play) => {
+                let _ = state.push(LoadingState::Paused);
+            }
+            // stay paused after the fullscreen app is gone
+            (ControlCommand::Pause, LoadingState::Paused) => fullscreen_pause.active = false,
+            (ControlCommand::Resume, LoadingState::Paused) => {
+                // and don't hide again for whatever fullscreen app is focused,
+                // until it loses focus
+                fullscreen_pause.overridden = true;
+                fullscreen_pause.active = false;
+                let _ = state.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Gets out of the way of fullscreen games and videos,
+/// and comes back once they lose focus.
+fn hide_for_fullscreen(
+    winit_windows: Option<Res<WinitWindows>>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+    mut last_check: Local<f64>,
+    mut fullscreen_pause: ResMut<FullscreenPause>,
+    mut state: ResMut<State<LoadingState>>,
+) {
+    let now = time.seconds_since_startup();
+    if now - *last_check < FULLSCREEN_CHECK_INTERVAL {
+        return;
+    }
+    *last_check = now;
+
+    let winit_windows = match winit_windows {
+        Some(winit_windows) => winit_windows,
+        None => return,
+    };
+    // any tank being covered is enough, since they all pause together
+    let fullscreen = settings.hide_on_fullscreen
+        && winit_windows
+            .windows
+            .values()
+            .any(crate::window::fullscreen_app_focused);
+
+    if !fullscreen {
+        fullscreen_pause.overridden = false;
+        if fullscreen_pause.active {
+            fullscreen_pause.active = false;
+            if *state.current() == LoadingState::Paused {
+                info!("Fullscreen app lost focus, coming back");
+                let _ = state.pop();
+            }
+        }
+    } else if !fullscreen_pause.active
+        && !fullscreen_pause.overridden
+        && matches!(
+            state.current(),
+            LoadingState::Loading | LoadingState::FillingWater | LoadingState::Play
+        )
+        && state.push(LoadingState::Paused).is_ok()
+    {
+        info!("Fullscreen app focused, hiding");
+        fullscreen_pause.active = true;
+    }
+}
+
+fn pause(mut clock: ResMut<SimClock>, winit_windows: Option<Res<WinitWindows>>, options: Res<Options>) {
+    clock.set_paused(true);
+    if let Some(winit_windows) = winit_windows {
+        for window in winit_windows.windows.values() {
+            crate::window::set_visible(window, &options, false);
+        }
+    }
+}
+
+fn throttle() {
+    std::thread::sleep(PAUSED_FRAME_TIME);
+}
+
+/// Also runs when draining straight from being paused.
+fn resume(mut clock: ResMut<SimClock>, winit_windows: Option<Res<WinitWindows>>, options: Res<Options>) {
+    clock.set_paused(false);
+    if let Some(winit_windows) = winit_windows {
+        for window in winit_windows.windows.values() {
+            crate::window::set_visible(window, &options, true);
+        }
+    }
+}
+
+fn refill_water(mut query: Query<&mut Water>, clock: Res<SimClock>) {
+    for mut water in query.iter_mut() {
+        water.start_time = clock.elapsed();
+        water.refilling = true;
+    }
+}
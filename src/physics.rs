@@ -0,0 +1,366 @@
+//! Dolphin physics, kept free of any Bevy or winit types
+//! so that it can be stepped without opening a window.
+
+use glam::DVec2;
+use serde::Deserialize;
+
+pub const STANDARD_GRAVITY: f64 = 9.80665;
+pub const MAX_STEP_TIME: f64 = 1.0 / 60.0;
+pub const DEFAULT_DRAG: f64 = 0.1;
+/// How far below the surface the dolphin's center has to be for it to be
+/// completely underwater. It's half underwater with its center at the surface.
+const BODY_DEPTH: f64 = 32.0;
+/// Sideways speed past which the dolphin faces the way it's swimming,
+/// rather than the way it rests above or below the water.
+const HEADING_SPEED: f64 = 5.0;
+
+/// How the simulation gets from one step to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Integrator {
+    /// Updates velocity, then position with the new velocity. Cheap and stable enough.
+    SemiImplicitEuler,
+    /// Second order, and keeps energy from drifting over long runs. Drag
+    /// is solved for implicitly, since it depends on the new velocity.
+    VelocityVerlet,
+    /// Classic fourth order Runge-Kutta, for the most accuracy per step.
+    Rk4,
+}
+
+impl Default for Integrator {
+    fn default() -> Self {
+        Self::Rk4
+    }
+}
+
+/// Tunables shared by every [`FaithSim`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimParams {
+    pub gravity: f64,
+    /// How much the water slows the dolphin down, scaled by how much of
+    /// it is underwater. Without any, it bobs up and down forever.
+    pub drag: f64,
+    /// Longest step the simulation takes at once, in seconds.
+    pub max_step_time: f64,
+    pub integrator: Integrator,
+}
+
+impl Default for SimParams {
+    fn default() -> Self {
+        Self {
+            gravity: STANDARD_GRAVITY,
+            drag: DEFAULT_DRAG,
+            max_step_time: MAX_STEP_TIME,
+            integrator: Integrator::default(),
+        }
+    }
+}
+
+/// Physical state of a single dolphin.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FaithSim {
+    pub position: DVec2,
+    pub velocity: DVec2,
+    /// Angle of the way the dolphin's facing, counterclockwise from
+    /// the right. Anything past straight up or down faces left.
+    pub rotation: f64,
+    /// Acceleration the dolphin gives itself by swimming,
+    /// on top of gravity and buoyancy. Only works underwater.
+    pub thrust: DVec2,
+    /// Set while something else (like the cursor) is moving the
+    /// dolphin around, which pauses gravity and buoyancy.
+    pub held: bool,
+}
+
+impl FaithSim {
+    pub fn new(position: DVec2) -> Self {
+        Self {
+            position,
+            ..Self::default()
+        }
+    }
+
+    /// Sets [`thrust`](Self::thrust) to swim towards `velocity`,
+    /// capped at `max_thrust` so that it can't turn on the spot.
+    pub fn swim_towards(&mut self, velocity: DVec2, gain: f64, max_thrust: f64) {
+        self.thrust = ((velocity - self.velocity) * gain).clamp_length_max(max_thrust);
+    }
+
+    /// Advances the simulation by `dt` seconds.
+    ///
+    /// `water_level` is the height of the water surface and `bounds`
+    /// is the size of the tank, both in the same units as `position`.
+    /// The step is subdivided into equal chunks of at most
+    /// `params.max_step_time` to guard against lag spikes.
+    /// Returns how many chunks that took.
+    pub fn step(&mut self, dt: f64, water_level: f64, bounds: DVec2, params: &SimParams) -> u32 {
+        if dt <= 0.0 {
+            return 0;
+        }
+        // rounding would otherwise add a tiny extra step to times that divide evenly
+        let substeps = (dt / params.max_step_time - 1e-9).ceil().max(1.0) as u32;
+        let delta = dt / substeps as f64;
+        for _ in 0..substeps {
+            self.substep(delta, water_level, bounds, params);
+        }
+        substeps
+    }
+
+    fn substep(&mut self, delta: f64, water_level: f64, bounds: DVec2, params: &SimParams) {
+        if !self.held {
+            let acceleration =
+                |position: DVec2, velocity: DVec2| self.acceleration(position, velocity, water_level, params);
+            let (position, velocity) = (self.position, self.velocity);
+            let (position, velocity) = match params.integrator {
+                Integrator::SemiImplicitEuler => {
+                    let velocity = velocity + acceleration(position, velocity) * delta;
+                    (position + velocity * delta, velocity)
+                }
+                Integrator::VelocityVerlet => {
+                    let start = acceleration(position, velocity);
+                    let position = position + velocity * delta + start * (0.5 * delta * delta);
+                    // the acceleration at the end depends on the velocity being
+                    // solved for, but only linearly through drag, so solve exactly
+                    let forces = self.forces(position, water_level, params);
+                    let damping = self.damping(position, water_level, params);
+                    let velocity = (velocity + (start + forces) * (0.5 * delta)) / (1.0 + 0.5 * delta * damping);
+                    (position, velocity)
+                }
+                Integrator::Rk4 => {
+                    let k1 = (velocity, acceleration(position, velocity));
+                    let k2_velocity = velocity + k1.1 * (0.5 * delta);
+                    let k2 = (k2_velocity, acceleration(position + k1.0 * (0.5 * delta), k2_velocity));
+                    let k3_velocity = velocity + k2.1 * (0.5 * delta);
+                    let k3 = (k3_velocity, acceleration(position + k2.0 * (0.5 * delta), k3_velocity));
+                    let k4_velocity = velocity + k3.1 * delta;
+                    let k4 = (k4_velocity, acceleration(position + k3.0 * delta, k4_velocity));
+                    (
+                        position + (k1.0 + 2.0 * k2.0 + 2.0 * k3.0 + k4.0) * (delta / 6.0),
+                        velocity + (k1.1 + 2.0 * k2.1 + 2.0 * k3.1 + k4.1) * (delta / 6.0),
+                    )
+                }
+            };
+            self.position = position;
+            self.velocity = velocity;
+        }
+
+        // Update rotation, pointing along the velocity near the surface
+        // and leveling out further away from it. the window has no
+        // size for a moment while it's being set up, so treat that as level
+        let dist_from_water = if bounds.y > 0.0 {
+            2.0 * (self.position.y - water_level).abs() / bounds.y
+        } else {
+            0.0
+        };
+        let dir = self.velocity.y * ((1.0 - dist_from_water).powf(2.0) - 0.01 * dist_from_water);
+        let rest = if self.position.y > water_level { 1.0 } else { -1.0 };
+        let heading = DVec2::new(rest + self.velocity.x / HEADING_SPEED, dir);
+        self.rotation = heading.y.atan2(heading.x);
+    }
+
+    fn acceleration(&self, position: DVec2, velocity: DVec2, water_level: f64, params: &SimParams) -> DVec2 {
+        self.forces(position, water_level, params) - velocity * self.damping(position, water_level, params)
+    }
+
+    /// Gravity above the water, and buoyancy and swimming below it.
+    fn forces(&self, position: DVec2, water_level: f64, params: &SimParams) -> DVec2 {
+        let depth = water_level - position.y;
+        if depth < 0.0 {
+            DVec2::new(0.0, -params.gravity)
+        } else {
+            DVec2::new(0.0, depth.sqrt()) + self.thrust
+        }
+    }
+
+    /// Drag for however much of the dolphin is underwater.
+    fn damping(&self, position: DVec2, water_level: f64, params: &SimParams) -> f64 {
+        let submerged = (0.5 + (water_level - position.y) / BODY_DEPTH).clamp(0.0, 1.0);
+        params.drag * submerged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::const_dvec2;
+
+    use super::*;
+
+    const BOUNDS: DVec2 = const_dvec2!([800.0, 600.0]);
+    /// Ten minutes, a frame at a time.
+    const FRAMES: usize = 60 * 60 * 10;
+    const INTEGRATORS: [Integrator; 3] = [
+        Integrator::SemiImplicitEuler,
+        Integrator::VelocityVerlet,
+        Integrator::Rk4,
+    ];
+
+    /// Kinetic plus potential energy per unit of mass.
+    fn energy(sim: &FaithSim, water_level: f64, params: &SimParams) -> f64 {
+        let height = sim.position.y - water_level;
+        let potential = if height > 0.0 {
+            params.gravity * height
+        } else {
+            // the integral of the buoyancy below
+            2.0 / 3.0 * (-height).powf(1.5)
+        };
+        0.5 * sim.velocity.length_squared() + potential
+    }
+
+    fn frictionless() -> SimParams {
+        SimParams {
+            drag: 0.0,
+            ..SimParams::default()
+        }
+    }
+
+    #[test]
+    fn bobs_to_the_same_height() {
+        let params = frictionless();
+        let mut sim = FaithSim::new(DVec2::new(0.0, 100.0));
+        let mut peaks = Vec::new();
+        for _ in 0..FRAMES {
+            let rising = sim.velocity.y > 0.0;
+            sim.step(MAX_STEP_TIME, 0.0, BOUNDS, &params);
+            if rising && sim.velocity.y <= 0.0 && sim.position.y > 0.0 {
+                peaks.push(sim.position.y);
+            }
+        }
+
+        assert!(peaks.len() > 10, "only bobbed {} times", peaks.len());
+        for peak in peaks {
+            assert!((peak - 100.0).abs() < 5.0, "bobbed up to {}", peak);
+        }
+    }
+
+    #[test]
+    fn rotation_is_never_nan() {
+        let params = SimParams::default();
+        for bounds in [BOUNDS, DVec2::ZERO] {
+            for start in [100.0, 0.0, -100.0] {
+                for held in [false, true] {
+                    let mut sim = FaithSim::new(DVec2::new(0.0, start));
+                    sim.held = held;
+                    for _ in 0..600 {
+                        sim.step(MAX_STEP_TIME, 0.0, bounds, &params);
+                        assert!(sim.rotation.is_finite(), "{:?} in {:?}", sim, bounds);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lag_spikes_are_subdivided() {
+        let params = frictionless();
+        let start = FaithSim::new(DVec2::new(0.0, 100.0));
+        let initial = energy(&start, 0.0, &params);
+
+        // a couple of seconds in one go lands where the same
+        // time does a frame at a time
+        let mut smooth = start;
+        for _ in 0..120 {
+            smooth.step(MAX_STEP_TIME, 0.0, BOUNDS, &params);
+        }
+        let mut spiky = start;
+        spiky.step(120.0 * MAX_STEP_TIME, 0.0, BOUNDS, &params);
+        assert!(smooth.position.distance(spiky.position) < 1e-6);
+        assert!(smooth.velocity.distance(spiky.velocity) < 1e-6);
+
+        // and energy doesn't blow up no matter how long the spikes are
+        let mut sim = start;
+        for _ in 0..1200 {
+            sim.step(0.5, 0.0, BOUNDS, &params);
+            let ratio = energy(&sim, 0.0, &params) / initial;
+            assert!((0.95..1.05).contains(&ratio), "energy changed by a factor of {}", ratio);
+        }
+    }
+
+    #[test]
+    fn drag_drains_energy() {
+        for integrator in INTEGRATORS {
+            let params = SimParams {
+                integrator,
+                ..SimParams::default()
+            };
+            for start in [100.0, -100.0] {
+                let mut sim = FaithSim::new(DVec2::new(0.0, start));
+                let initial = energy(&sim, 0.0, &params);
+                // steps that straddle the surface, where gravity gives way to buoyancy
+                // partway through, gain a little energy, so compare whole bobs instead
+                let mut last = f64::INFINITY;
+                for _ in 0..FRAMES {
+                    let below = sim.position.y <= 0.0;
+                    sim.step(MAX_STEP_TIME, 0.0, BOUNDS, &params);
+                    if below && sim.position.y > 0.0 {
+                        let current = energy(&sim, 0.0, &params);
+                        // until it's down to the last pixel or so of bobbing
+                        if last > 2.0 {
+                            assert!(current < last, "{:?} went from {} to {}", integrator, last, current);
+                        }
+                        last = current;
+                    }
+                }
+                assert!(energy(&sim, 0.0, &params) < initial * 0.01, "{:?}", integrator);
+            }
+        }
+    }
+
+    #[test]
+    fn settles_at_the_water_level() {
+        for integrator in INTEGRATORS {
+            let params = SimParams {
+                integrator,
+                ..SimParams::default()
+            };
+            for start in [100.0, -100.0] {
+                let mut sim = FaithSim::new(DVec2::new(0.0, start));
+                for frame in 0..FRAMES {
+                    sim.step(MAX_STEP_TIME, 0.0, BOUNDS, &params);
+                    if frame >= FRAMES - 600 {
+                        assert!(sim.position.y.abs() < 1.0, "{:?} at {:?}", integrator, sim);
+                        assert!(sim.velocity.length() < 1.0, "{:?} at {:?}", integrator, sim);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn energy_is_bounded_without_drag() {
+        for integrator in INTEGRATORS {
+            let params = SimParams {
+                integrator,
+                ..frictionless()
+            };
+            for start in [100.0, -100.0] {
+                let mut sim = FaithSim::new(DVec2::new(0.0, start));
+                let initial = energy(&sim, 0.0, &params);
+                for _ in 0..FRAMES {
+                    sim.step(MAX_STEP_TIME, 0.0, BOUNDS, &params);
+                    let ratio = energy(&sim, 0.0, &params) / initial;
+                    assert!(
+                        (0.95..1.05).contains(&ratio),
+                        "{:?} changed energy by a factor of {}",
+                        integrator,
+                        ratio
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn even_steps_are_not_subdivided_further() {
+        let params = SimParams::default();
+        let mut sim = FaithSim::new(DVec2::new(0.0, 100.0));
+        for substeps in 1..=120 {
+            assert_eq!(
+                sim.step(substeps as f64 * MAX_STEP_TIME, 0.0, BOUNDS, &params),
+                substeps
+            );
+        }
+        assert_eq!(sim.step(0.05, 0.0, BOUNDS, &params), 3);
+        assert_eq!(sim.step(1.5 * MAX_STEP_TIME, 0.0, BOUNDS, &params), 2);
+        assert_eq!(sim.step(0.0, 0.0, BOUNDS, &params), 0);
+    }
+}
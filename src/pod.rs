@@ -0,0 +1,109 @@
+//! More than one dolphin at once. The first dolphin leads, while the rest
+//! swim alongside it, jump along with it, and keep out of each other's way.
+
+use bevy::math::DVec2;
+use bevy::prelude::*;
+
+use crate::swim::Swimmer;
+use crate::{Faith, LoadingState};
+
+/// How far apart dolphins swimming side by side are.
+const POD_SPACING: f64 = 160.0;
+/// Dolphins closer than this nudge each other apart.
+const PERSONAL_SPACE: f64 = 96.0;
+const AVOIDANCE_THRUST: f64 = 20.0;
+
+/// Tints for each dolphin, so they can be told apart.
+const PALETTE: [Color; 4] = [
+    Color::WHITE,
+    Color::rgb(0.75, 0.85, 1.0),
+    Color::rgb(1.0, 0.8, 0.85),
+    Color::rgb(0.85, 0.8, 1.0),
+];
+
+/// Where a dolphin swims in the pod. The leader is slot 0.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PodMember {
+    pub slot: usize,
+}
+
+impl PodMember {
+    /// How far to the side of the leader this dolphin swims,
+    /// alternating right and left as the pod grows.
+    pub fn offset(self) -> f64 {
+        let rank = ((self.slot + 1) / 2) as f64;
+        if self.slot % 2 == 1 {
+            rank * POD_SPACING
+        } else {
+            -rank * POD_SPACING
+        }
+    }
+
+    pub fn color(self) -> Color {
+        PALETTE[self.slot % PALETTE.len()]
+    }
+}
+
+pub struct PodPlugin;
+
+impl Plugin for PodPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(LoadingState::Play)
+                .with_system(keep_formation.before("swim"))
+                .with_system(avoid_collisions.after("swim").before("update_faith")),
+        );
+    }
+
+    fn name(&self) -> &str {
+        "PodPlugin"
+    }
+}
+
+/// Has everyone but the leader swim alongside the leader.
+fn keep_formation(mut query: Query<(&Faith, &PodMember, &mut Swimmer)>) {
+    let leader = query
+        .iter()
+        .find(|(_, member, _)| member.slot == 0)
+        .map(|(faith, ..)| faith.sim.position.x);
+
+    for (_, member, mut swimmer) in query.iter_mut() {
+        swimmer.follow = match leader {
+            Some(leader) if member.slot > 0 => Some(leader + member.offset()),
+            _ => None,
+        };
+    }
+}
+
+/// Nudges dolphins apart on top of whatever they're already swimming towards.
+fn avoid_collisions(mut query: Query<(Entity, &mut Faith)>) {
+    let positions = query
+        .iter()
+        .map(|(entity, faith)| (entity, faith.sim.position))
+        .collect::<Vec<_>>();
+
+    for (entity, mut faith) in query.iter_mut() {
+        let push =
+            positions
+                .iter()
+                .filter(|(other, _)| *other != entity)
+                .fold(DVec2::ZERO, |push, (other, position)| {
+                    let offset = faith.sim.position - *position;
+                    let distance = offset.length();
+                    if distance < PERSONAL_SPACE {
+                        // two dolphins in the exact same spot still need to split up somehow
+                        let away = if distance > f64::EPSILON {
+                            offset / distance
+                        } else if entity < *other {
+                            DVec2::X
+                        } else {
+                            -DVec2::X
+                        };
+                        push + away * (1.0 - distance / PERSONAL_SPACE)
+                    } else {
+                        push
+                    }
+                });
+        faith.sim.thrust += push * AVOIDANCE_THRUST;
+    }
+}
@@ -0,0 +1,172 @@
+//! Line-delimited JSON protocol spoken over the control socket.
+//!
+//! Every request is a single [`ControlCommand`] on its own line, and the
+//! server answers each one with a single [`Response`] line. This module is
+//! shared with the `dolphinctl` binary, so it shouldn't depend on Bevy.
+
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+pub const SOCKET_ENV_VAR: &str = "DOLPHIN_SOCKET";
+pub const SOCKET_NAME: &str = "trustworthy-dolphin.sock";
+
+pub const MAX_SPAWN_FISHES: u32 = 256;
+pub const MAX_SAY_LENGTH: usize = 96;
+
+/// For example `{"command": "spawn-fish", "count": 5}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum ControlCommand {
+    SpawnFish { count: u32 },
+    Drain,
+    Say { text: String },
+    Pause,
+    Resume,
+    Trick { trick: Trick },
+}
+
+/// Everything the dolphin can be doing. [`Trick::Cruise`] is
+/// its usual swimming around, and asking for it ends any trick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Trick {
+    Cruise,
+    DiveDeep,
+    BreachJump,
+    Backflip,
+    TailWalk,
+}
+
+impl Trick {
+    pub const ALL: [Trick; 5] = [
+        Self::Cruise,
+        Self::DiveDeep,
+        Self::BreachJump,
+        Self::Backflip,
+        Self::TailWalk,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Cruise => "cruise",
+            Self::DiveDeep => "dive-deep",
+            Self::BreachJump => "breach-jump",
+            Self::Backflip => "backflip",
+            Self::TailWalk => "tail-walk",
+        }
+    }
+
+    /// All of the tricks' names, listed for help text.
+    pub fn names() -> String {
+        let names = Self::ALL.map(|trick| format!("`{}`", trick));
+        let (last, rest) = names.split_last().unwrap();
+        format!("{}, or {}", rest.join(", "), last)
+    }
+}
+
+impl Default for Trick {
+    fn default() -> Self {
+        Self::Cruise
+    }
+}
+
+impl Display for Trick {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Trick {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter().find(|trick| trick.name() == s).ok_or(())
+    }
+}
+
+impl ControlCommand {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::SpawnFish { count } if *count == 0 || *count > MAX_SPAWN_FISHES => {
+                Err(format!("`count` must be between 1 and {}", MAX_SPAWN_FISHES))
+            }
+            Self::Say { text } if text.trim().is_empty() => Err("`text` can't be empty".to_string()),
+            Self::Say { text } if text.chars().count() > MAX_SAY_LENGTH => {
+                Err(format!("`text` can't be longer than {} characters", MAX_SAY_LENGTH))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// For example `{"ok": false, "error": "unknown variant `jump`"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Response {
+    pub fn ok() -> Self {
+        Self { ok: true, error: None }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            error: Some(message.into()),
+        }
+    }
+}
+
+/// `$DOLPHIN_SOCKET` if it's set, otherwise a socket
+/// in `$XDG_RUNTIME_DIR` or the temp directory.
+pub fn socket_path() -> PathBuf {
+    if let Some(path) = std::env::var_os(SOCKET_ENV_VAR).filter(|path| !path.is_empty()) {
+        return PathBuf::from(path);
+    }
+    match std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join(SOCKET_NAME),
+        // the temp directory is shared, so keep users from clobbering each other's sockets
+        None => {
+            let user = std::env::var("USER").unwrap_or_default();
+            std::env::temp_dir().join(format!("{}-{}", user, SOCKET_NAME))
+        }
+    }
+}
+
+/// Sends `command` to the aquarium listening on `socket` and waits
+/// for its answer, turning an error response into an `Err`.
+#[cfg(unix)]
+#[allow(dead_code)]
+pub fn send(socket: &Path, command: &ControlCommand) -> Result<(), String> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let mut stream =
+        UnixStream::connect(socket).map_err(|err| format!("couldn't connect to {}: {}", socket.display(), err))?;
+    let mut request = serde_json::to_string(command).unwrap();
+    request.push('\n');
+    stream.write_all(request.as_bytes()).map_err(|err| err.to_string())?;
+
+    let mut line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut line)
+        .map_err(|err| err.to_string())?;
+    let response: Response = serde_json::from_str(&line).map_err(|err| format!("invalid response: {}", err))?;
+    if response.ok {
+        Ok(())
+    } else {
+        Err(response.error.unwrap_or_else(|| "unknown error".to_string()))
+    }
+}
+
+#[cfg(not(unix))]
+#[allow(dead_code)]
+pub fn send(_socket: &Path, _command: &ControlCommand) -> Result<(), String> {
+    Err("the control socket is only supported on Unix".to_string())
+}
@@ -0,0 +1,92 @@
+//! Keeping everything in the tanks in place when their windows
+//! are resized, moved to another monitor, or change scale factor.
+
+use bevy::prelude::*;
+use bevy::sprite::Mesh2dHandle;
+
+use crate::tank::{Tank, TankId, Tanks};
+use crate::waves::WaveSurface;
+use crate::{wave_mesh, Bubbles, Faith, Fish, Water};
+
+pub struct ResizePlugin;
+
+impl Plugin for ResizePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(CoreStage::PreUpdate, reanchor.after("window_size"));
+    }
+
+    fn name(&self) -> &str {
+        "ResizePlugin"
+    }
+}
+
+/// Rebuilds the water and stretches everything else along with
+/// any tank whose bounds changed since the last frame.
+#[allow(clippy::type_complexity)]
+fn reanchor(
+    tanks: Res<Tanks>,
+    mut previous: Local<Vec<Tank>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut water_query: Query<(&mut Water, &mut Transform, &Mesh2dHandle, &TankId)>,
+    mut fish_query: Query<(&mut Transform, &TankId), (With<Fish>, Without<Water>)>,
+    mut bubbles_query: Query<(&mut Transform, &TankId), (With<Bubbles>, Without<Water>, Without<Fish>)>,
+    mut faith_query: Query<&mut Faith>,
+    mut camera_query: Query<(&Camera, &mut Transform), (Without<Water>, Without<Fish>, Without<Bubbles>)>,
+) {
+    if !tanks.is_changed() {
+        return;
+    }
+
+    for (index, (old, tank)) in previous.iter().zip(tanks.tanks.iter()).enumerate() {
+        if old.center == tank.center && old.size == tank.size {
+            continue;
+        }
+        let id = TankId(index);
+        let scale = if old.size.cmpgt(Vec2::ZERO).all() {
+            tank.size / old.size
+        } else {
+            Vec2::ONE
+        };
+        let move_point = |point: Vec3| (tank.center + (point.truncate() - old.center) * scale).extend(point.z);
+
+        for (mut water, mut transform, mesh, water_tank) in water_query.iter_mut() {
+            if *water_tank != id {
+                continue;
+            }
+            // the water's height is set every frame, but its mesh
+            // and texture tiling only ever fit one size
+            transform.translation.x = tank.center.x;
+            if old.size != tank.size {
+                water.surface = WaveSurface::new(tank.size.x as f64);
+                if let Some(mesh) = meshes.get_mut(&mesh.0) {
+                    *mesh = wave_mesh(tank.size, &water.surface);
+                }
+            }
+        }
+        for (mut transform, fish_tank) in fish_query.iter_mut() {
+            if *fish_tank == id {
+                transform.translation = move_point(transform.translation);
+            }
+        }
+        for (mut transform, bubbles_tank) in bubbles_query.iter_mut() {
+            if *bubbles_tank == id {
+                transform.translation = move_point(transform.translation);
+            }
+        }
+        for (camera, mut transform) in camera_query.iter_mut() {
+            if camera.window == tank.window {
+                transform.translation.x = tank.center.x;
+                transform.translation.y = tank.center.y;
+            }
+        }
+
+        // the dolphin lives in the first tank, which is always centered on the origin
+        if index == 0 {
+            for mut faith in faith_query.iter_mut() {
+                faith.sim.position *= scale.as_dvec2();
+            }
+        }
+    }
+
+    *previous = tanks.tanks.clone();
+}
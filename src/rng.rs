@@ -0,0 +1,71 @@
+//! The one source of randomness behind everything that spawns, so that a
+//! run can be replayed exactly by giving it the same seed.
+
+use std::ops::{Deref, DerefMut};
+
+use bevy::prelude::{info, warn, App, Plugin};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::cli::Options;
+
+pub const SEED_ENV_VAR: &str = "DOLPHIN_SEED";
+
+/// Random number generator shared by all spawners.
+///
+/// Seeded from `--seed <u64>` or the `DOLPHIN_SEED` environment
+/// variable if either is given, so that a run can be replayed.
+pub struct SimRng(StdRng);
+
+impl SimRng {
+    pub fn from_seed(seed: u64) -> Self {
+        Self(StdRng::seed_from_u64(seed))
+    }
+}
+
+impl Deref for SimRng {
+    type Target = StdRng;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for SimRng {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+pub struct RngPlugin;
+
+impl Plugin for RngPlugin {
+    fn build(&self, app: &mut App) {
+        let seed = app
+            .world
+            .get_resource::<Options>()
+            .and_then(|options| options.seed)
+            .or_else(seed_from_env)
+            .unwrap_or_else(rand::random);
+        info!("Using RNG seed {}", seed);
+        app.insert_resource(SimRng::from_seed(seed));
+    }
+
+    fn name(&self) -> &str {
+        "RngPlugin"
+    }
+}
+
+fn seed_from_env() -> Option<u64> {
+    let seed = std::env::var(SEED_ENV_VAR).ok()?;
+    match seed.parse() {
+        Ok(seed) => Some(seed),
+        Err(_) => {
+            warn!(
+                "Ignoring `{}={}`, since it isn't an unsigned 64-bit integer",
+                SEED_ENV_VAR, seed
+            );
+            None
+        }
+    }
+}
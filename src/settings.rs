@@ -0,0 +1,421 @@
+//! Tunables loaded from `trustworthy-dolphin/config.toml` in the user's
+//! config directory, and reloaded whenever that file changes.
+//!
+//! Command-line options take priority over the config file.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::io::ErrorKind;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use crossbeam_channel::Receiver;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Deserialize;
+
+use crate::cli::Options;
+use crate::physics::{Integrator, SimParams, DEFAULT_DRAG, MAX_STEP_TIME, STANDARD_GRAVITY};
+use crate::{Bubbles, Faith, Fish};
+
+pub const CONFIG_DIR: &str = "trustworthy-dolphin";
+pub const CONFIG_FILE: &str = "config.toml";
+/// Smallest `max_step_time` allowed, since a lag spike
+/// would otherwise be split into millions of steps.
+const MIN_STEP_TIME: f64 = 1e-4;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub gravity: f64,
+    /// How much the water slows the dolphin down. Without any, it bobs up and down forever.
+    pub drag: f64,
+    /// How much faster than real time the dolphin's physics runs.
+    pub speed: f64,
+    pub max_step_time: f64,
+    /// `semi-implicit-euler`, `velocity-verlet`, or `rk4`.
+    pub integrator: Integrator,
+    pub max_bubbles: u32,
+    pub max_fishes: u32,
+    /// How many dolphins there are. Only read when they first show up.
+    pub pod_size: u32,
+    /// Let the dolphin swim off one side and come back in on the
+    /// other, instead of turning around at the edges.
+    pub wrap_around: bool,
+    /// Range of speeds new fishes swim at, in units per second.
+    pub fish_speed: (f64, f64),
+    /// Scale of the dolphin and fishes. Bubbles are drawn at half this.
+    pub sprite_scale: f32,
+    /// Range of how many bubbles tall each column of bubbles is.
+    pub bubble_height: (usize, usize),
+    /// Hide the aquarium while another app is fullscreen.
+    pub hide_on_fullscreen: bool,
+    /// Keys that aren't settings, kept so that [`Settings::validate`]
+    /// can point out which line they're on.
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            gravity: STANDARD_GRAVITY,
+            drag: DEFAULT_DRAG,
+            speed: 6.0,
+            max_step_time: MAX_STEP_TIME,
+            integrator: Integrator::default(),
+            max_bubbles: 16,
+            max_fishes: 32,
+            pod_size: 1,
+            wrap_around: false,
+            fish_speed: (20.0, 100.0),
+            sprite_scale: 4.0,
+            bubble_height: (4, 16),
+            hide_on_fullscreen: true,
+            unknown: BTreeMap::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Reads settings from `path`, falling back to
+    /// the defaults if the file doesn't exist.
+    pub fn load(path: &Path) -> Result<Self, SettingsError> {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(SettingsError::new(path, None, err.to_string())),
+        };
+
+        let settings: Self = toml::from_str(&source).map_err(|err| {
+            let line = err.line_col().map(|(line, _)| line + 1);
+            SettingsError::new(path, line, err.to_string())
+        })?;
+        settings
+            .validate()
+            .map_err(|(key, message)| SettingsError::new(path, line_of(&source, key), message))?;
+        Ok(settings)
+    }
+
+    /// Checks that every setting is known and in range,
+    /// returning the offending key if one isn't.
+    fn validate(&self) -> Result<(), (&str, String)> {
+        fn positive(key: &'static str, value: f64) -> Result<(), (&'static str, String)> {
+            if value > 0.0 && value.is_finite() {
+                Ok(())
+            } else {
+                Err((key, format!("`{}` must be a positive number, but is {}", key, value)))
+            }
+        }
+
+        fn non_negative(key: &'static str, value: f64) -> Result<(), (&'static str, String)> {
+            if value >= 0.0 && value.is_finite() {
+                Ok(())
+            } else {
+                Err((key, format!("`{}` can't be negative, but is {}", key, value)))
+            }
+        }
+
+        fn at_least(key: &'static str, value: f64, min: f64) -> Result<(), (&'static str, String)> {
+            if value >= min && value.is_finite() {
+                Ok(())
+            } else {
+                Err((key, format!("`{}` must be at least {}, but is {}", key, min, value)))
+            }
+        }
+
+        fn range<T: PartialOrd + Display>(key: &'static str, (min, max): (T, T)) -> Result<(), (&'static str, String)> {
+            if min <= max {
+                Ok(())
+            } else {
+                Err((key, format!("`{}` must be [min, max], but {} > {}", key, min, max)))
+            }
+        }
+
+        if let Some(key) = self.unknown.keys().next() {
+            return Err((key, format!("unknown setting `{}`", key)));
+        }
+        positive("gravity", self.gravity)?;
+        non_negative("drag", self.drag)?;
+        positive("speed", self.speed)?;
+        at_least("max_step_time", self.max_step_time, MIN_STEP_TIME)?;
+        positive("pod_size", self.pod_size as f64)?;
+        positive("fish_speed", self.fish_speed.0)?;
+        range("fish_speed", self.fish_speed)?;
+        positive("sprite_scale", self.sprite_scale as f64)?;
+        positive("bubble_height", self.bubble_height.0 as f64)?;
+        range("bubble_height", self.bubble_height)
+    }
+
+    /// Applies any command-line options that override the config file.
+    pub fn with_overrides(mut self, options: &Options) -> Self {
+        if let Some(speed) = options.speed {
+            self.speed = speed;
+        }
+        if let Some(max_bubbles) = options.max_bubbles {
+            self.max_bubbles = max_bubbles;
+        }
+        if let Some(max_fishes) = options.max_fishes {
+            self.max_fishes = max_fishes;
+        }
+        if let Some(pod_size) = options.pod_size {
+            self.pod_size = pod_size;
+        }
+        self.wrap_around |= options.wrap_around;
+        self.sprite_scale *= options.scale;
+        self
+    }
+
+    pub fn sim_params(&self) -> SimParams {
+        SimParams {
+            gravity: self.gravity,
+            drag: self.drag,
+            max_step_time: self.max_step_time,
+            integrator: self.integrator,
+        }
+    }
+
+    pub fn fish_speed(&self) -> RangeInclusive<f64> {
+        self.fish_speed.0..=self.fish_speed.1
+    }
+
+    pub fn bubble_height(&self) -> RangeInclusive<usize> {
+        self.bubble_height.0..=self.bubble_height.1
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettingsError {
+    pub path: PathBuf,
+    /// 1-based line the error is on, if it's known.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl SettingsError {
+    fn new(path: &Path, line: Option<usize>, message: String) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            line,
+            message,
+        }
+    }
+}
+
+impl Display for SettingsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.path.display(), line, self.message),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+/// Finds the 1-based line that sets `key`.
+fn line_of(source: &str, key: &str) -> Option<usize> {
+    source
+        .lines()
+        .position(|line| {
+            line.trim_start()
+                .strip_prefix(key)
+                .map_or(false, |rest| rest.trim_start().starts_with('='))
+        })
+        .map(|i| i + 1)
+}
+
+/// `$XDG_CONFIG_HOME/trustworthy-dolphin/config.toml`, or the
+/// platform's usual config directory if that isn't set.
+pub fn default_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            if cfg!(target_os = "windows") {
+                std::env::var_os("APPDATA").map(PathBuf::from)
+            } else {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))
+            }
+        })?;
+    Some(config_home.join(CONFIG_DIR).join(CONFIG_FILE))
+}
+
+fn config_dir(path: &Path) -> &Path {
+    path.parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."))
+}
+
+/// Watches the config file's directory, since many
+/// editors save by replacing the file entirely.
+pub struct SettingsWatcher {
+    path: PathBuf,
+    events: Receiver<notify::Result<Event>>,
+    _watcher: RecommendedWatcher,
+}
+
+impl SettingsWatcher {
+    pub fn new(path: PathBuf) -> notify::Result<Self> {
+        let (sender, events) = crossbeam_channel::unbounded();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let _ = sender.send(event);
+        })?;
+        watcher.watch(config_dir(&path), RecursiveMode::NonRecursive)?;
+        Ok(Self {
+            path,
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    /// Whether the config file changed since this was last called.
+    fn changed(&self) -> bool {
+        let mut changed = false;
+        for event in self.events.try_iter() {
+            match event {
+                Ok(event) => {
+                    changed |= !matches!(event.kind, EventKind::Access(_))
+                        && event.paths.iter().any(|path| path.file_name() == self.path.file_name());
+                }
+                Err(err) => warn!("Error while watching {}: {}", self.path.display(), err),
+            }
+        }
+        changed
+    }
+}
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        let options = app.world.get_resource::<Options>().cloned().unwrap_or_default();
+        let path = options.config.clone().or_else(default_path);
+
+        let settings = match &path {
+            Some(path) => Settings::load(path).unwrap_or_else(|err| {
+                error!("{}, using the default settings", err);
+                Settings::default()
+            }),
+            None => Settings::default(),
+        };
+        app.insert_resource(settings.with_overrides(&options))
+            .add_system(rescale_sprites);
+
+        // keep headless runs reproducible, and don't bother watching
+        // for a config file if there isn't even a directory for it
+        if let (Some(path), None) = (path, &options.headless) {
+            if !config_dir(&path).is_dir() {
+                return;
+            }
+            match SettingsWatcher::new(path.clone()) {
+                Ok(watcher) => {
+                    app.insert_resource(watcher)
+                        .add_system_to_stage(CoreStage::PreUpdate, reload_settings);
+                }
+                Err(err) => warn!("Couldn't watch {}, so it won't be reloaded: {}", path.display(), err),
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "SettingsPlugin"
+    }
+}
+
+fn reload_settings(watcher: Res<SettingsWatcher>, options: Res<Options>, mut settings: ResMut<Settings>) {
+    if !watcher.changed() {
+        return;
+    }
+
+    // keep the old settings around if the new ones are broken
+    match Settings::load(&watcher.path) {
+        Ok(new_settings) => {
+            let new_settings = new_settings.with_overrides(&options);
+            if *settings != new_settings {
+                info!("Reloaded settings from {}", watcher.path.display());
+                *settings = new_settings;
+            }
+        }
+        Err(err) => error!("{}", err),
+    }
+}
+
+/// Keeps sprites that have already been spawned at the current `sprite_scale`.
+fn rescale_sprites(
+    settings: Res<Settings>,
+    mut query: Query<(&mut Transform, Option<&Bubbles>), Or<(With<Faith>, With<Fish>, With<Bubbles>)>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    for (mut transform, bubbles) in query.iter_mut() {
+        let scale = if bubbles.is_some() {
+            settings.sprite_scale / 2.0
+        } else {
+            settings.sprite_scale
+        };
+        transform.scale = Vec2::splat(scale).extend(0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `source` to a config file of its own for each test, since they run in parallel.
+    fn load(test: &str, source: &str) -> Result<Settings, SettingsError> {
+        let path = std::env::temp_dir().join(format!("trustworthy-dolphin-{}-{}.toml", test, std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        let settings = Settings::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        settings
+    }
+
+    #[test]
+    fn loads_settings_over_the_defaults() {
+        let settings = load("valid", "# faster\nspeed = 2.5\nfish_speed = [10, 30.5]\n").unwrap();
+        assert_eq!(settings.speed, 2.5);
+        assert_eq!(settings.fish_speed, (10.0, 30.5));
+        assert_eq!(settings.gravity, Settings::default().gravity);
+    }
+
+    #[test]
+    fn missing_files_fall_back_on_the_defaults() {
+        let path = std::env::temp_dir().join(format!("trustworthy-dolphin-missing-{}.toml", std::process::id()));
+        assert_eq!(Settings::load(&path), Ok(Settings::default()));
+    }
+
+    #[test]
+    fn rejects_unknown_keys_on_their_line() {
+        let err = load("unknown", "speed = 2.0\n\ngravitee = 3.0\n").unwrap_err();
+        assert_eq!(err.line, Some(3));
+        assert!(err.message.contains("`gravitee`"), "{}", err);
+    }
+
+    #[test]
+    fn reports_out_of_range_values_on_their_line() {
+        let err = load("negative", "gravity = 9.8\n  drag = -1.0\n").unwrap_err();
+        assert_eq!(err.line, Some(2));
+        assert!(err.message.contains("`drag`"), "{}", err);
+
+        let err = load("step", "max_step_time = 1e-9\n").unwrap_err();
+        assert_eq!(err.line, Some(1));
+        assert!(err.message.contains("`max_step_time`"), "{}", err);
+    }
+
+    #[test]
+    fn reports_syntax_errors_where_they_are() {
+        let err = load("syntax", "speed = 2.0\ndrag = \n").unwrap_err();
+        assert_eq!(err.line, Some(2));
+        assert!(err.message.contains("line 2 column 8"), "{}", err);
+    }
+
+    #[test]
+    fn finds_keys_without_matching_prefixes() {
+        let source = "max_bubbles_extra = 1\n max_bubbles= 2\n";
+        assert_eq!(line_of(source, "max_bubbles"), Some(2));
+        assert_eq!(line_of(source, "max_fishes"), None);
+    }
+}
@@ -0,0 +1,262 @@
+//! Speech bubbles for `dolphinctl say`.
+//!
+//! There aren't any fonts in the assets, so text is drawn
+//! with a tiny built-in 3x5 pixel font, one sprite per pixel.
+
+use bevy::prelude::*;
+
+use crate::clock::SimClock;
+use crate::protocol::ControlCommand;
+use crate::window::WindowSize;
+use crate::{Faith, LoadingState};
+
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+/// Size of a single font pixel on screen.
+const PIXEL_SIZE: f32 = 2.0;
+/// Padding around the text, in font pixels.
+const PADDING: usize = 3;
+const MAX_LINE_CHARS: usize = 24;
+const MAX_LINES: usize = 4;
+
+/// How far above the dolphin the bubble floats.
+const OFFSET_Y: f32 = 80.0;
+const BASE_LIFETIME: f32 = 2.5;
+const LIFETIME_PER_CHAR: f32 = 0.08;
+const FADE_TIME: f32 = 0.5;
+
+const BUBBLE_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.9);
+const TEXT_COLOR: Color = Color::rgb(0.1, 0.15, 0.3);
+
+/// Rows of each glyph, top to bottom, with `#` for filled pixels.
+/// Lowercase letters are drawn as uppercase, and anything else as `?`.
+#[rustfmt::skip]
+const GLYPHS: &[(char, [&str; GLYPH_HEIGHT])] = &[
+    ('A', ["###", "#.#", "###", "#.#", "#.#"]),
+    ('B', ["##.", "#.#", "##.", "#.#", "##."]),
+    ('C', ["###", "#..", "#..", "#..", "###"]),
+    ('D', ["##.", "#.#", "#.#", "#.#", "##."]),
+    ('E', ["###", "#..", "##.", "#..", "###"]),
+    ('F', ["###", "#..", "##.", "#..", "#.."]),
+    ('G', ["###", "#..", "#.#", "#.#", "###"]),
+    ('H', ["#.#", "#.#", "###", "#.#", "#.#"]),
+    ('I', ["###", ".#.", ".#.", ".#.", "###"]),
+    ('J', ["..#", "..#", "..#", "#.#", "###"]),
+    ('K', ["#.#", "#.#", "##.", "#.#", "#.#"]),
+    ('L', ["#..", "#..", "#..", "#..", "###"]),
+    ('M', ["#.#", "###", "###", "#.#", "#.#"]),
+    ('N', ["##.", "#.#", "#.#", "#.#", "#.#"]),
+    ('O', ["###", "#.#", "#.#", "#.#", "###"]),
+    ('P', ["###", "#.#", "###", "#..", "#.."]),
+    ('Q', ["###", "#.#", "#.#", "###", "..#"]),
+    ('R', ["###", "#.#", "##.", "#.#", "#.#"]),
+    ('S', ["###", "#..", "###", "..#", "###"]),
+    ('T', ["###", ".#.", ".#.", ".#.", ".#."]),
+    ('U', ["#.#", "#.#", "#.#", "#.#", "###"]),
+    ('V', ["#.#", "#.#", "#.#", "#.#", ".#."]),
+    ('W', ["#.#", "#.#", "###", "###", "#.#"]),
+    ('X', ["#.#", "#.#", ".#.", "#.#", "#.#"]),
+    ('Y', ["#.#", "#.#", ".#.", ".#.", ".#."]),
+    ('Z', ["###", "..#", ".#.", "#..", "###"]),
+    ('0', ["###", "#.#", "#.#", "#.#", "###"]),
+    ('1', [".#.", "##.", ".#.", ".#.", "###"]),
+    ('2', ["###", "..#", "###", "#..", "###"]),
+    ('3', ["###", "..#", ".##", "..#", "###"]),
+    ('4', ["#.#", "#.#", "###", "..#", "..#"]),
+    ('5', ["###", "#..", "###", "..#", "###"]),
+    ('6', ["###", "#..", "###", "#.#", "###"]),
+    ('7', ["###", "..#", "..#", ".#.", ".#."]),
+    ('8', ["###", "#.#", "###", "#.#", "###"]),
+    ('9', ["###", "#.#", "###", "..#", "###"]),
+    (' ', ["...", "...", "...", "...", "..."]),
+    ('.', ["...", "...", "...", "...", ".#."]),
+    (',', ["...", "...", "...", ".#.", "#.."]),
+    ('!', [".#.", ".#.", ".#.", "...", ".#."]),
+    ('?', ["###", "..#", ".##", "...", ".#."]),
+    ('\'', [".#.", ".#.", "...", "...", "..."]),
+    ('-', ["...", "...", "###", "...", "..."]),
+    (':', ["...", ".#.", "...", ".#.", "..."]),
+    ('/', ["..#", "..#", ".#.", "#..", "#.."]),
+    ('(', ["..#", ".#.", ".#.", ".#.", "..#"]),
+    (')', ["#..", ".#.", ".#.", ".#.", "#.."]),
+];
+
+#[derive(Component)]
+struct SpeechBubble {
+    age: f32,
+    lifetime: f32,
+}
+
+pub struct SpeechPlugin;
+
+impl Plugin for SpeechPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(LoadingState::Play)
+                .with_system(say.label("say"))
+                .with_system(update_speech_bubbles.after("say")),
+        );
+    }
+
+    fn name(&self) -> &str {
+        "SpeechPlugin"
+    }
+}
+
+fn glyph(c: char) -> [&'static str; GLYPH_HEIGHT] {
+    let c = c.to_ascii_uppercase();
+    GLYPHS
+        .iter()
+        .find(|(glyph, _)| *glyph == c)
+        .or_else(|| GLYPHS.iter().find(|(glyph, _)| *glyph == '?'))
+        .unwrap()
+        .1
+}
+
+/// Greedily wraps `text` into at most [`MAX_LINES`] lines of
+/// [`MAX_LINE_CHARS`], splitting words that are too long by themselves.
+fn wrap(text: &str) -> Vec<Vec<char>> {
+    let mut lines: Vec<Vec<char>> = vec![];
+    let mut line = vec![];
+    for word in text.split_whitespace() {
+        let word = word.chars().collect::<Vec<_>>();
+        if !line.is_empty() && line.len() + 1 + word.len() > MAX_LINE_CHARS {
+            lines.push(std::mem::take(&mut line));
+        }
+        for chunk in word.chunks(MAX_LINE_CHARS) {
+            if !line.is_empty() && line.len() + 1 + chunk.len() > MAX_LINE_CHARS {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.extend_from_slice(chunk);
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines.truncate(MAX_LINES);
+    lines
+}
+
+fn say(
+    mut commands: Commands,
+    mut control: EventReader<ControlCommand>,
+    bubble_query: Query<Entity, With<SpeechBubble>>,
+    faith_query: Query<&Faith>,
+    window_size: Res<WindowSize>,
+) {
+    let text = match control
+        .iter()
+        .filter_map(|command| match command {
+            ControlCommand::Say { text } => Some(text),
+            _ => None,
+        })
+        .last()
+    {
+        Some(text) => text,
+        None => return,
+    };
+
+    // only one thing can be said at a time
+    for entity in bubble_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let lines = wrap(text);
+    let columns = lines.iter().map(Vec::len).max().unwrap_or(0);
+    let text_size = Vec2::new(
+        (columns * (GLYPH_WIDTH + 1)).saturating_sub(1) as f32,
+        (lines.len() * (GLYPH_HEIGHT + 2)).saturating_sub(2) as f32,
+    );
+    let bubble_size = (text_size + Vec2::splat(2.0 * PADDING as f32)) * PIXEL_SIZE;
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: BUBBLE_COLOR,
+                custom_size: Some(bubble_size),
+                ..Sprite::default()
+            },
+            transform: Transform::from_translation(
+                bubble_position(&faith_query, bubble_size, window_size.0).extend(3.0),
+            ),
+            ..SpriteBundle::default()
+        })
+        .insert(SpeechBubble {
+            age: 0.0,
+            lifetime: BASE_LIFETIME + LIFETIME_PER_CHAR * text.chars().count() as f32,
+        })
+        .with_children(|builder| {
+            let top_left = Vec2::new(-text_size.x, text_size.y) / 2.0;
+            for (row, line) in lines.iter().enumerate() {
+                for (column, c) in line.iter().enumerate() {
+                    for (y, pixels) in glyph(*c).iter().enumerate() {
+                        for (x, _) in pixels.char_indices().filter(|(_, pixel)| *pixel == '#') {
+                            let pos = top_left
+                                + Vec2::new(
+                                    (column * (GLYPH_WIDTH + 1) + x) as f32 + 0.5,
+                                    -((row * (GLYPH_HEIGHT + 2) + y) as f32 + 0.5),
+                                );
+                            builder.spawn_bundle(SpriteBundle {
+                                sprite: Sprite {
+                                    color: TEXT_COLOR,
+                                    custom_size: Some(Vec2::splat(PIXEL_SIZE)),
+                                    ..Sprite::default()
+                                },
+                                transform: Transform::from_translation((pos * PIXEL_SIZE).extend(0.1)),
+                                ..SpriteBundle::default()
+                            });
+                        }
+                    }
+                }
+            }
+        });
+}
+
+/// Just above the dolphin, but still on screen.
+fn bubble_position(faith_query: &Query<&Faith>, bubble_size: Vec2, window_size: Vec2) -> Vec2 {
+    let faith_position = faith_query
+        .iter()
+        .next()
+        .map_or(Vec2::ZERO, |faith| faith.sim.position.as_vec2());
+    let max = (window_size / 2.0 - bubble_size / 2.0).max(Vec2::ZERO);
+    (faith_position + Vec2::new(0.0, OFFSET_Y)).clamp(-max, max)
+}
+
+fn update_speech_bubbles(
+    mut commands: Commands,
+    mut bubble_query: Query<(Entity, &mut SpeechBubble, &mut Transform, &mut Sprite, &Children)>,
+    mut pixel_query: Query<&mut Sprite, Without<SpeechBubble>>,
+    faith_query: Query<&Faith>,
+    window_size: Res<WindowSize>,
+    clock: Res<SimClock>,
+) {
+    for component in bubble_query.iter_mut() {
+        let (entity, mut bubble, mut transform, mut sprite, children): (
+            Entity,
+            Mut<SpeechBubble>,
+            Mut<Transform>,
+            Mut<Sprite>,
+            &Children,
+        ) = component;
+
+        bubble.age += clock.delta_seconds_f64() as f32;
+        if bubble.age >= bubble.lifetime {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        let bubble_size = sprite.custom_size.unwrap_or_default();
+        transform.translation = bubble_position(&faith_query, bubble_size, window_size.0).extend(3.0);
+
+        let alpha = ((bubble.lifetime - bubble.age) / FADE_TIME).min(1.0);
+        sprite.color.set_a(BUBBLE_COLOR.a() * alpha);
+        for child in children.iter() {
+            if let Ok(mut pixel) = pixel_query.get_mut(*child) {
+                pixel.color.set_a(alpha);
+            }
+        }
+    }
+}
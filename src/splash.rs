@@ -0,0 +1,109 @@
+//! Droplets thrown up whenever the dolphin crosses the water surface.
+
+use bevy::math::DVec2;
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::clock::SimClock;
+use crate::rng::SimRng;
+use crate::LoadingState;
+
+/// Below this speed the dolphin slips through the surface without any droplets.
+const MIN_SPLASH_SPEED: f64 = 60.0;
+/// How many droplets each unit of impact speed is worth.
+const DROPLETS_PER_SPEED: f64 = 1.0 / 16.0;
+const MAX_DROPLETS_PER_SPLASH: u32 = 32;
+
+const DROPLET_GRAVITY: f32 = 600.0;
+const DROPLET_SIZE: f32 = 4.0;
+const DROPLET_COLOR: Color = Color::rgb(0.8, 0.93, 1.0);
+
+/// Sent whenever the dolphin enters or leaves the water.
+///
+/// `velocity` is the dolphin's on-screen velocity in units per second,
+/// so a negative `velocity.y` means it's diving in and a positive one
+/// means it's jumping out.
+#[derive(Debug, Clone, Copy)]
+pub struct FaithCrossedSurface {
+    pub position: DVec2,
+    pub velocity: DVec2,
+}
+
+#[derive(Component)]
+struct Droplet {
+    pub velocity: Vec2,
+    pub age: f32,
+    pub lifetime: f32,
+}
+
+pub struct SplashPlugin;
+
+impl Plugin for SplashPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<FaithCrossedSurface>()
+            .add_system_set(
+                SystemSet::on_update(LoadingState::Play)
+                    .with_system(spawn_droplets.after("update_faith"))
+                    .with_system(update_droplets),
+            )
+            // droplets already in the air still need to land
+            .add_system_set(SystemSet::on_update(LoadingState::Draining).with_system(update_droplets));
+    }
+
+    fn name(&self) -> &str {
+        "SplashPlugin"
+    }
+}
+
+fn spawn_droplets(mut commands: Commands, mut events: EventReader<FaithCrossedSurface>, mut rng: ResMut<SimRng>) {
+    for event in events.iter() {
+        let speed = event.velocity.length();
+        if speed < MIN_SPLASH_SPEED {
+            continue;
+        }
+
+        // harder hits throw more droplets further
+        let count = ((speed * DROPLETS_PER_SPEED) as u32).min(MAX_DROPLETS_PER_SPLASH);
+        for _ in 0..count {
+            let velocity = DVec2::new(rng.gen_range(-0.4..=0.4) * speed, rng.gen_range(0.2..=0.5) * speed);
+
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: DROPLET_COLOR,
+                        custom_size: Some(Vec2::splat(DROPLET_SIZE)),
+                        ..Sprite::default()
+                    },
+                    transform: Transform::from_translation(event.position.as_vec2().extend(2.0)),
+                    ..SpriteBundle::default()
+                })
+                .insert(Droplet {
+                    velocity: velocity.as_vec2(),
+                    age: 0.0,
+                    lifetime: rng.gen_range(0.5..=1.2),
+                });
+        }
+    }
+}
+
+fn update_droplets(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Droplet, &mut Transform, &mut Sprite)>,
+    clock: Res<SimClock>,
+) {
+    let delta = clock.delta_seconds_f64() as f32;
+    for component in query.iter_mut() {
+        let (entity, mut droplet, mut transform, mut sprite): (Entity, Mut<Droplet>, Mut<Transform>, Mut<Sprite>) =
+            component;
+
+        droplet.age += delta;
+        if droplet.age >= droplet.lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        droplet.velocity.y -= DROPLET_GRAVITY * delta;
+        transform.translation += (droplet.velocity * delta).extend(0.0);
+        sprite.color.set_a(1.0 - droplet.age / droplet.lifetime);
+    }
+}
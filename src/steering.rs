@@ -0,0 +1,176 @@
+//! Steering behaviours that push fishes around, kept free of any
+//! Bevy types like [`boids`](crate::boids). Each behaviour is a
+//! [`Steering`] force, and a fish's acceleration is the sum of all
+//! the behaviours acting on it.
+
+use glam::Vec2;
+
+use crate::boids::Boid;
+
+/// How strongly fishes get back to swimming across at their own speed.
+const CRUISE_WEIGHT: f32 = 0.8;
+
+/// Fishes start turning back down this far below the water's surface,
+/// or back up this far above the bottom of the tank.
+const DEPTH_MARGIN: f32 = 32.0;
+const DEPTH_WEIGHT: f32 = 4.0;
+
+/// Vertical acceleration of the slow up and down drifting.
+const WANDER_STRENGTH: f32 = 12.0;
+/// How fast fishes drift up and down, in radians per second.
+const WANDER_FREQUENCY: f32 = 0.7;
+
+/// Acceleration of a fish darting away from right next to a threat.
+const FLEE_STRENGTH: f32 = 480.0;
+/// How much faster than usual a terrified fish swims.
+pub const FLEE_SPEEDUP: f32 = 1.5;
+/// How long it takes a terrified fish to calm down, in seconds.
+const CALM_DOWN_TIME: f32 = 2.0;
+
+pub trait Steering {
+    /// Acceleration this behaviour wants to give `boid`.
+    fn force(&self, boid: &Boid) -> Vec2;
+}
+
+/// Adds up the forces of every behaviour acting on `boid`.
+pub fn total_force(boid: &Boid, behaviors: &[&dyn Steering]) -> Vec2 {
+    behaviors
+        .iter()
+        .fold(Vec2::ZERO, |total, behavior| total + behavior.force(boid))
+}
+
+/// Steers back towards swimming across at `velocity`.
+pub struct Cruise {
+    pub velocity: Vec2,
+}
+
+impl Steering for Cruise {
+    fn force(&self, boid: &Boid) -> Vec2 {
+        (self.velocity - boid.velocity) * CRUISE_WEIGHT
+    }
+}
+
+/// Keeps fishes between the water's `surface` and the `bottom` of the tank.
+pub struct Depth {
+    pub surface: f32,
+    pub bottom: f32,
+}
+
+impl Steering for Depth {
+    fn force(&self, boid: &Boid) -> Vec2 {
+        let below_surface = self.surface - DEPTH_MARGIN - boid.position.y;
+        let above_bottom = boid.position.y - self.bottom - DEPTH_MARGIN;
+        let push = if below_surface < 0.0 {
+            below_surface
+        } else if above_bottom < 0.0 {
+            -above_bottom
+        } else {
+            0.0
+        };
+        Vec2::new(0.0, push * DEPTH_WEIGHT)
+    }
+}
+
+/// Gentle up and down drifting, offset by `phase` so that
+/// fishes don't all bob up and down in lockstep.
+pub struct Wander {
+    pub time: f32,
+    pub phase: f32,
+}
+
+impl Steering for Wander {
+    fn force(&self, _boid: &Boid) -> Vec2 {
+        Vec2::new(0.0, (self.time * WANDER_FREQUENCY + self.phase).sin() * WANDER_STRENGTH)
+    }
+}
+
+/// Darting away from a `threat` that comes within `radius`.
+pub struct Flee {
+    pub threat: Vec2,
+    pub radius: f32,
+}
+
+impl Flee {
+    /// How scared `boid` is of the threat, from 0 when it's
+    /// out of range to 1 when it's right on top of the fish.
+    pub fn fear(&self, boid: &Boid) -> f32 {
+        (1.0 - boid.position.distance(self.threat) / self.radius).max(0.0)
+    }
+}
+
+impl Steering for Flee {
+    fn force(&self, boid: &Boid) -> Vec2 {
+        let away = (boid.position - self.threat).normalize_or_zero();
+        // something right on top of a fish doesn't give it a direction to go
+        let away = if away == Vec2::ZERO { Vec2::Y } else { away };
+        away * FLEE_STRENGTH * self.fear(boid)
+    }
+}
+
+/// Fear left over after `delta` seconds without anything new to be scared of.
+pub fn calm_down(fear: f32, delta: f32) -> f32 {
+    (fear - delta / CALM_DOWN_TIME).max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boid_at(x: f32, y: f32) -> Boid {
+        Boid {
+            position: Vec2::new(x, y),
+            velocity: Vec2::new(40.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn flee_pushes_away_inside_its_radius() {
+        let flee = Flee {
+            threat: Vec2::new(10.0, -20.0),
+            radius: 100.0,
+        };
+        let mut last = f32::INFINITY;
+        for distance in [0.0, 10.0, 50.0, 99.0] {
+            for direction in [Vec2::X, -Vec2::X, Vec2::Y, Vec2::new(-0.6, 0.8)] {
+                let position = flee.threat + direction * distance;
+                let force = flee.force(&boid_at(position.x, position.y));
+                assert!(force.length() > 0.0, "no push at {}", position);
+                if distance > 0.0 {
+                    assert!(
+                        force.normalize().dot(direction) > 0.999,
+                        "pushed {} at {}",
+                        force,
+                        position
+                    );
+                }
+            }
+            // and harder the closer it is
+            let strength = flee.force(&boid_at(flee.threat.x + distance, flee.threat.y)).length();
+            assert!(strength < last);
+            last = strength;
+        }
+    }
+
+    #[test]
+    fn flee_does_nothing_outside_its_radius() {
+        let flee = Flee {
+            threat: Vec2::new(10.0, -20.0),
+            radius: 100.0,
+        };
+        for distance in [101.0, 150.0, 500.0] {
+            for direction in [Vec2::X, -Vec2::Y, Vec2::new(0.6, 0.8)] {
+                let position = flee.threat + direction * distance;
+                let boid = boid_at(position.x, position.y);
+                assert_eq!(flee.fear(&boid), 0.0);
+                assert_eq!(flee.force(&boid), Vec2::ZERO);
+            }
+        }
+    }
+
+    #[test]
+    fn fear_wears_off() {
+        assert_eq!(calm_down(1.0, CALM_DOWN_TIME), 0.0);
+        assert_eq!(calm_down(0.0, 1.0), 0.0);
+        assert!(calm_down(1.0, 0.5) < 1.0);
+    }
+}
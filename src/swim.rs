@@ -0,0 +1,121 @@
+//! The dolphin's everyday swimming back and forth across its tank,
+//! whenever it isn't hunting or doing a trick. Like those, this only
+//! ever steers through the dolphin's thrust, leaving bobbing up and
+//! down to buoyancy.
+
+use bevy::math::DVec2;
+use bevy::prelude::*;
+
+use crate::clock::SimClock;
+use crate::hunt::Hunter;
+use crate::protocol::Trick;
+use crate::settings::Settings;
+use crate::tank::{TankId, Tanks};
+use crate::tricks::Tricks;
+use crate::{Faith, LoadingState};
+
+/// Sideways speed the dolphin cruises at, in the physics' own units.
+const CRUISE_SPEED: f64 = 12.0;
+/// How much the cruising speed wanders, as a fraction of it.
+const WANDER_STRENGTH: f64 = 0.5;
+/// How fast the cruising speed wanders, in radians per second.
+const WANDER_FREQUENCY: f64 = 0.3;
+/// The dolphin turns around this far from the edges of its tank.
+const EDGE_MARGIN: f64 = 96.0;
+/// When wrapping around, how far past an edge the dolphin gets
+/// before coming back in on the other side, so that it's out of sight.
+const WRAP_MARGIN: f64 = 128.0;
+
+/// How hard followers swim to keep up with their spot next to the leader.
+const FOLLOW_GAIN: f64 = 0.05;
+const STEERING_GAIN: f64 = 2.0;
+const MAX_THRUST: f64 = 30.0;
+
+#[derive(Component, Debug, Clone)]
+pub struct Swimmer {
+    /// Which way the dolphin's cruising, 1 for right and -1 for left.
+    pub heading: f64,
+    /// Where to swim alongside instead of cruising on its own,
+    /// for dolphins following their pod's leader.
+    pub follow: Option<f64>,
+    /// Keeps dolphins from all wandering in lockstep.
+    phase: f64,
+}
+
+impl Swimmer {
+    pub fn new(phase: f64) -> Self {
+        Self {
+            heading: 1.0,
+            follow: None,
+            phase,
+        }
+    }
+}
+
+pub struct SwimPlugin;
+
+impl Plugin for SwimPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(LoadingState::Play)
+                .with_system(swim.label("swim").after("hunt").before("update_faith")),
+        );
+    }
+
+    fn name(&self) -> &str {
+        "SwimPlugin"
+    }
+}
+
+fn swim(
+    mut query: Query<(&mut Faith, &mut Swimmer, &Hunter, &Tricks)>,
+    tanks: Res<Tanks>,
+    clock: Res<SimClock>,
+    settings: Res<Settings>,
+) {
+    let time = clock.elapsed().as_secs_f64();
+    // the dolphin lives in the first tank, which is centered on the origin
+    let half_width = tanks.get(TankId(0)).size.x as f64 / 2.0;
+
+    for (mut faith, mut swimmer, hunter, tricks) in query.iter_mut() {
+        if faith.sim.held {
+            continue;
+        }
+
+        if settings.wrap_around && faith.sim.position.x.abs() > half_width + WRAP_MARGIN {
+            faith.sim.position.x -= faith.sim.position.x.signum() * 2.0 * (half_width + WRAP_MARGIN);
+        }
+
+        if tricks.current != Trick::Cruise || hunter.is_chasing() {
+            continue;
+        }
+
+        // head back in, turning through an arc since the thrust is capped
+        if !settings.wrap_around {
+            let x = faith.sim.position.x;
+            if x > half_width - EDGE_MARGIN && swimmer.heading > 0.0 {
+                swimmer.heading = -1.0;
+            } else if x < -half_width + EDGE_MARGIN && swimmer.heading < 0.0 {
+                swimmer.heading = 1.0;
+            }
+        }
+
+        let desired = match swimmer.follow {
+            Some(spot) => {
+                let mut offset = spot - faith.sim.position.x;
+                if settings.wrap_around {
+                    // go whichever way around is shorter
+                    let width = 2.0 * (half_width + WRAP_MARGIN);
+                    offset -= (offset / width).round() * width;
+                }
+                offset * FOLLOW_GAIN
+            }
+            None => {
+                let wander = 1.0 + WANDER_STRENGTH * (time * WANDER_FREQUENCY + swimmer.phase).sin();
+                swimmer.heading * CRUISE_SPEED * wander
+            }
+        };
+        let thrust = ((desired - faith.sim.velocity.x) * STEERING_GAIN).clamp(-MAX_THRUST, MAX_THRUST);
+        faith.sim.thrust = DVec2::new(thrust, 0.0);
+    }
+}
@@ -0,0 +1,161 @@
+//! One tank of water per monitor, each shown in its own window.
+//!
+//! Tanks are laid out in world space the same way their monitors are
+//! laid out on the desktop, with the first tank centered on the origin,
+//! and each window's camera only looks at its own tank.
+
+use bevy::core_pipeline::{draw_2d_graph, node, Transparent2d};
+use bevy::prelude::*;
+use bevy::render::camera::{ActiveCameras, ExtractedCameraNames};
+use bevy::render::render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext, SlotValue};
+use bevy::render::render_phase::RenderPhase;
+use bevy::render::renderer::RenderContext;
+use bevy::render::{RenderApp, RenderStage};
+use bevy::window::WindowId;
+
+/// Cameras for every tank but the first, which uses the usual 2d camera.
+const TANK_CAMERA_PREFIX: &str = "tank_camera_";
+const TANK_CAMERAS_DRIVER: &str = "tank_cameras_driver";
+
+#[derive(Debug, Clone)]
+pub struct Tank {
+    pub window: WindowId,
+    /// Center of the tank in world space.
+    pub center: Vec2,
+    /// Logical size of the tank's window.
+    pub size: Vec2,
+}
+
+#[derive(Debug, Clone)]
+pub struct Tanks {
+    /// The dolphin always lives in the first one.
+    pub tanks: Vec<Tank>,
+    /// Whether fishes swim through every tank instead of just their own.
+    pub span: bool,
+}
+
+impl Tanks {
+    pub fn single(size: Vec2) -> Self {
+        Self {
+            tanks: vec![Tank {
+                window: WindowId::primary(),
+                center: Vec2::ZERO,
+                size,
+            }],
+            span: false,
+        }
+    }
+
+    pub fn get(&self, tank: TankId) -> &Tank {
+        &self.tanks[tank.0]
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = TankId> {
+        (0..self.tanks.len()).map(TankId)
+    }
+
+    /// The tank that `point` is in, if any.
+    pub fn at(&self, point: Vec2) -> Option<TankId> {
+        self.ids().find(|id| {
+            let tank = self.get(*id);
+            (point - tank.center).abs().cmple(tank.size / 2.0).all()
+        })
+    }
+
+    /// Left and right edges of where fishes in `tank` swim between.
+    pub fn lane(&self, tank: TankId) -> (f32, f32) {
+        let edges = |tank: &Tank| (tank.center.x - tank.size.x / 2.0, tank.center.x + tank.size.x / 2.0);
+        if self.span {
+            self.tanks.iter().map(edges).fold(
+                (f32::INFINITY, f32::NEG_INFINITY),
+                |(left, right), (tank_left, tank_right)| (left.min(tank_left), right.max(tank_right)),
+            )
+        } else {
+            edges(self.get(tank))
+        }
+    }
+}
+
+impl Default for Tanks {
+    fn default() -> Self {
+        Self::single(Vec2::ZERO)
+    }
+}
+
+/// Which tank water, bubbles, and fishes belong to.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TankId(pub usize);
+
+pub struct TankPlugin;
+
+impl Plugin for TankPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Tanks>();
+
+        // Bevy only renders the default 2d camera by itself,
+        // so the other tanks' cameras need a render graph node too
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.add_system_to_stage(RenderStage::Extract, extract_tank_camera_phases);
+            let mut graph = render_app.world.get_resource_mut::<RenderGraph>().unwrap();
+            graph.add_node(TANK_CAMERAS_DRIVER, TankCamerasDriver);
+            graph
+                .add_node_edge(node::MAIN_PASS_DEPENDENCIES, TANK_CAMERAS_DRIVER)
+                .unwrap();
+        }
+    }
+
+    fn name(&self) -> &str {
+        "TankPlugin"
+    }
+}
+
+/// Spawns a camera looking at each tank.
+pub fn setup_cameras(mut commands: Commands, tanks: Res<Tanks>, mut active_cameras: Option<ResMut<ActiveCameras>>) {
+    for (index, tank) in tanks.tanks.iter().enumerate() {
+        let mut camera = OrthographicCameraBundle::new_2d();
+        camera.transform.translation.x = tank.center.x;
+        camera.transform.translation.y = tank.center.y;
+        if index > 0 {
+            let name = format!("{}{}", TANK_CAMERA_PREFIX, index);
+            if let Some(active_cameras) = active_cameras.as_mut() {
+                active_cameras.add(&name);
+            }
+            camera.camera.name = Some(name);
+            camera.camera.window = tank.window;
+        }
+        commands.spawn_bundle(camera);
+    }
+}
+
+fn extract_tank_camera_phases(mut commands: Commands, tanks: Res<Tanks>, active_cameras: Res<ActiveCameras>) {
+    for index in 1..tanks.tanks.len() {
+        let name = format!("{}{}", TANK_CAMERA_PREFIX, index);
+        if let Some(entity) = active_cameras.get(&name).and_then(|camera| camera.entity) {
+            commands
+                .get_or_spawn(entity)
+                .insert(RenderPhase::<Transparent2d>::default());
+        }
+    }
+}
+
+/// Runs the 2d render graph once for each extra tank's camera.
+struct TankCamerasDriver;
+
+impl Node for TankCamerasDriver {
+    fn run(
+        &self,
+        graph: &mut RenderGraphContext,
+        _render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let extracted_cameras = world.get_resource::<ExtractedCameraNames>().unwrap();
+        for (_, camera) in extracted_cameras
+            .entities
+            .iter()
+            .filter(|(name, _)| name.starts_with(TANK_CAMERA_PREFIX))
+        {
+            graph.run_sub_graph(draw_2d_graph::NAME, vec![SlotValue::Entity(*camera)])?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,358 @@
+//! The dolphin's repertoire of tricks, as a little state machine on top
+//! of its physics. Like hunting, tricks only ever steer the dolphin
+//! through its thrust, so gravity and buoyancy still get the last word.
+//!
+//! Tricks happen on their own every now and then, but can also be asked
+//! for with `--trick`, the number keys, or `dolphinctl trick`.
+
+use std::f32::consts::{FRAC_PI_2, TAU};
+use std::time::Duration;
+
+use bevy::math::DVec2;
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::animation::{FaithAnimations, BACKFLIP, BREACH, DIVE, SWIM, TAIL_WALK};
+use crate::cli::Options;
+use crate::clock::SimClock;
+use crate::hunt::Hunter;
+use crate::protocol::{ControlCommand, Trick};
+use crate::rng::SimRng;
+use crate::tank::{TankId, Tanks};
+use crate::{Faith, LoadingState};
+
+/// Chance per second of starting a trick once the dolphin's been cruising a while.
+const TRICK_CHANCE: f64 = 0.08;
+const STEERING_GAIN: f64 = 3.0;
+const MAX_THRUST: f64 = 60.0;
+
+const DIVE_SPEED: f64 = 35.0;
+/// Dives level out this far above the bottom of the tank.
+const DIVE_FLOOR_MARGIN: f64 = 64.0;
+const BREACH_SPEED: f64 = 70.0;
+const TAIL_WALK_SPEED: f64 = 15.0;
+/// Just enough to keep the dolphin's tail at the surface.
+const TAIL_WALK_LIFT: f64 = 20.0;
+
+/// Keys that ask for each trick.
+const HOTKEYS: [(KeyCode, Trick); 5] = [
+    (KeyCode::Key0, Trick::Cruise),
+    (KeyCode::Key1, Trick::DiveDeep),
+    (KeyCode::Key2, Trick::BreachJump),
+    (KeyCode::Key3, Trick::Backflip),
+    (KeyCode::Key4, Trick::TailWalk),
+];
+
+/// How a trick looks and how long it's kept up for.
+struct TrickInfo {
+    tag: &'static str,
+    /// In seconds. Cruising lasts at least this long before another trick.
+    duration: f64,
+    /// Seconds before the trick can happen again on its own.
+    cooldown: f64,
+    /// Tricks that can follow this one, and how likely each one is.
+    next: &'static [(Trick, f64)],
+}
+
+fn info(trick: Trick) -> TrickInfo {
+    match trick {
+        Trick::Cruise => TrickInfo {
+            tag: SWIM,
+            duration: 6.0,
+            cooldown: 0.0,
+            next: &[
+                (Trick::DiveDeep, 3.0),
+                (Trick::BreachJump, 3.0),
+                (Trick::Backflip, 1.0),
+                (Trick::TailWalk, 1.0),
+            ],
+        },
+        Trick::DiveDeep => TrickInfo {
+            tag: DIVE,
+            duration: 2.0,
+            cooldown: 10.0,
+            // coming back up from deep down makes for the best jumps
+            next: &[(Trick::Cruise, 2.0), (Trick::BreachJump, 2.0), (Trick::Backflip, 1.0)],
+        },
+        Trick::BreachJump => TrickInfo {
+            tag: BREACH,
+            duration: 3.0,
+            cooldown: 15.0,
+            next: &[(Trick::Cruise, 1.0)],
+        },
+        Trick::Backflip => TrickInfo {
+            tag: BACKFLIP,
+            duration: 3.0,
+            cooldown: 30.0,
+            next: &[(Trick::Cruise, 1.0)],
+        },
+        Trick::TailWalk => TrickInfo {
+            tag: TAIL_WALK,
+            duration: 3.0,
+            cooldown: 30.0,
+            next: &[(Trick::Cruise, 1.0)],
+        },
+    }
+}
+
+/// Which trick the dolphin's doing.
+#[derive(Component, Debug, Default)]
+pub struct Tricks {
+    pub current: Trick,
+    since: Duration,
+    /// When each trick is off cooldown, in the same order as [`Trick::ALL`].
+    ready_at: [Duration; Trick::ALL.len()],
+    /// Which way the dolphin's heading during a tail walk.
+    direction: f64,
+}
+
+impl Tricks {
+    fn start(&mut self, trick: Trick, now: Duration) {
+        self.current = trick;
+        self.since = now;
+        self.ready_at[trick as usize] = now + Duration::from_secs_f64(info(trick).cooldown);
+    }
+
+    /// How far along the current trick is, from 0 to 1.
+    fn progress(&self, now: Duration) -> f64 {
+        ((now - self.since).as_secs_f64() / info(self.current).duration).min(1.0)
+    }
+}
+
+/// A trick that's been asked for, which every dolphin starts right away.
+#[derive(Debug, Default)]
+struct RequestedTrick(Option<Trick>);
+
+pub struct TrickPlugin;
+
+impl Plugin for TrickPlugin {
+    fn build(&self, app: &mut App) {
+        let options = app.world.get_resource::<Options>().cloned().unwrap_or_default();
+        app.insert_resource(RequestedTrick(options.trick))
+            .add_system_set(
+                SystemSet::on_update(LoadingState::Play)
+                    // tricks, then hunting, then swimming each get to steer the dolphin
+                    // in turn, and the first two draw from the shared rng after the fishes
+                    .with_system(request_tricks.label("request_tricks").after("spawn_fishes"))
+                    .with_system(perform_tricks.label("perform_tricks").after("request_tricks"))
+                    .with_system(pose_tricks.after("update_faith")),
+            )
+            .add_system_set(SystemSet::on_enter(LoadingState::Draining).with_system(stop_tricks));
+    }
+
+    fn name(&self) -> &str {
+        "TrickPlugin"
+    }
+}
+
+fn request_tricks(
+    mut control: EventReader<ControlCommand>,
+    keys: Res<Input<KeyCode>>,
+    mut requested: ResMut<RequestedTrick>,
+) {
+    for command in control.iter() {
+        if let ControlCommand::Trick { trick } = command {
+            requested.0 = Some(*trick);
+        }
+    }
+    for (key, trick) in HOTKEYS {
+        if keys.just_pressed(key) {
+            requested.0 = Some(trick);
+        }
+    }
+}
+
+fn perform_tricks(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Faith, &mut Tricks, &Hunter)>,
+    mut requested: ResMut<RequestedTrick>,
+    animations: Res<FaithAnimations>,
+    tanks: Res<Tanks>,
+    mut rng: ResMut<SimRng>,
+    clock: Res<SimClock>,
+) {
+    let now = clock.elapsed();
+    let pod = query.iter().count() > 1;
+    let request = requested.0.take();
+    let bottom = -tanks.get(TankId(0)).size.y as f64 / 2.0;
+
+    // everyone decides first, so that the rest of the pod can join in on a jump right away
+    let mut decisions = Vec::new();
+    for (entity, faith, tricks, hunter) in query.iter() {
+        let next = if request.is_some() {
+            // asking for a trick skips its cooldown
+            request
+        } else if tricks.progress(now) < 1.0 || faith.sim.held || hunter.is_chasing() {
+            None
+        } else if tricks.current == Trick::Cruise {
+            if rng.gen_bool((TRICK_CHANCE * clock.delta_seconds_f64()).min(1.0)) {
+                pick_next(tricks, now, &mut rng)
+            } else {
+                None
+            }
+        } else {
+            pick_next(tricks, now, &mut rng).or(Some(Trick::Cruise))
+        };
+        decisions.push((entity, next));
+    }
+    let jump = decisions
+        .iter()
+        .find_map(|(_, next)| next.filter(|trick| matches!(trick, Trick::BreachJump | Trick::Backflip)))
+        .filter(|_| pod);
+
+    for (entity, next) in decisions {
+        let (_, mut faith, mut tricks, _) = query.get_mut(entity).unwrap();
+        let next = match jump {
+            Some(jump) if tricks.current != jump => Some(jump),
+            _ => next,
+        };
+
+        if let Some(next) = next {
+            let previous = tricks.current;
+            tricks.start(next, now);
+            tricks.direction = if faith.sim.position.x > 0.0 { -1.0 } else { 1.0 };
+            if next == Trick::Cruise {
+                faith.sim.thrust = DVec2::ZERO;
+            }
+            // looping tags don't end by themselves
+            let still_playing = animations.get(info(previous).tag).map_or(false, |tag| tag.looping);
+            if !animations.play(&mut commands, entity, info(next).tag) && still_playing {
+                animations.play(&mut commands, entity, SWIM);
+            }
+        }
+
+        let target = match tricks.current {
+            Trick::Cruise => continue,
+            Trick::DiveDeep if faith.sim.position.y < bottom + DIVE_FLOOR_MARGIN => DVec2::ZERO,
+            Trick::DiveDeep => DVec2::new(0.0, -DIVE_SPEED),
+            Trick::BreachJump | Trick::Backflip => DVec2::new(0.0, BREACH_SPEED),
+            Trick::TailWalk => DVec2::new(tricks.direction * TAIL_WALK_SPEED, TAIL_WALK_LIFT),
+        };
+        faith.sim.swim_towards(target, STEERING_GAIN, MAX_THRUST);
+    }
+}
+
+/// Picks what comes after the current trick, leaving out anything still on cooldown.
+fn pick_next(tricks: &Tricks, now: Duration, rng: &mut SimRng) -> Option<Trick> {
+    let choices = info(tricks.current)
+        .next
+        .iter()
+        .filter(|(trick, _)| tricks.ready_at[*trick as usize] <= now)
+        .collect::<Vec<_>>();
+    let total = choices.iter().map(|(_, weight)| weight).sum::<f64>();
+    if total <= 0.0 {
+        return None;
+    }
+
+    let mut roll = rng.gen_range(0.0..total);
+    for (trick, weight) in &choices {
+        if roll < *weight {
+            return Some(*trick);
+        }
+        roll -= weight;
+    }
+    choices.last().map(|(trick, _)| *trick)
+}
+
+/// Turns the dolphin on top of the rotation its physics gives it.
+fn pose_tricks(mut query: Query<(&Tricks, &mut Transform), With<Faith>>, clock: Res<SimClock>) {
+    for (tricks, mut transform) in query.iter_mut() {
+        let progress = tricks.progress(clock.elapsed()) as f32;
+        match tricks.current {
+            Trick::Backflip => {
+                // ease in and out of a full turn
+                let turn = progress * progress * (3.0 - 2.0 * progress);
+                transform.rotation *= Quat::from_rotation_z(TAU * turn);
+            }
+            Trick::TailWalk => {
+                // straight up, whichever way it's walking
+                transform.rotation = Quat::from_rotation_z(FRAC_PI_2);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Lets the dolphin sink along with the draining water.
+fn stop_tricks(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Faith, &mut Tricks)>,
+    animations: Res<FaithAnimations>,
+    clock: Res<SimClock>,
+) {
+    for (entity, mut faith, mut tricks) in query.iter_mut() {
+        if tricks.current != Trick::Cruise {
+            tricks.start(Trick::Cruise, clock.elapsed());
+            faith.sim.thrust = DVec2::ZERO;
+            animations.play(&mut commands, entity, SWIM);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cruising, with every trick in `cooling_down` started at `Duration::ZERO`.
+    fn cruising_after(cooling_down: &[Trick]) -> Tricks {
+        let mut tricks = Tricks::default();
+        for trick in cooling_down {
+            tricks.start(*trick, Duration::ZERO);
+        }
+        tricks.start(Trick::Cruise, Duration::ZERO);
+        tricks
+    }
+
+    #[test]
+    fn leaves_out_tricks_on_cooldown() {
+        let mut rng = SimRng::from_seed(1);
+        let tricks = cruising_after(&[Trick::DiveDeep, Trick::BreachJump]);
+        for _ in 0..1000 {
+            let next = pick_next(&tricks, Duration::from_secs(5), &mut rng);
+            assert!(matches!(next, Some(Trick::Backflip | Trick::TailWalk)), "{:?}", next);
+        }
+        // once they've cooled down, they're back in the running
+        let now = Duration::from_secs_f64(info(Trick::BreachJump).cooldown);
+        assert!((0..1000).any(|_| pick_next(&tricks, now, &mut rng) == Some(Trick::BreachJump)));
+    }
+
+    #[test]
+    fn picks_tricks_by_weight() {
+        let mut rng = SimRng::from_seed(2);
+        let tricks = cruising_after(&[]);
+        let picks = 20000;
+        let mut counts = [0; Trick::ALL.len()];
+        for _ in 0..picks {
+            counts[pick_next(&tricks, Duration::ZERO, &mut rng).unwrap() as usize] += 1;
+        }
+
+        let next = info(Trick::Cruise).next;
+        let total = next.iter().map(|(_, weight)| weight).sum::<f64>();
+        assert_eq!(counts[Trick::Cruise as usize], 0);
+        for (trick, weight) in next {
+            let share = counts[*trick as usize] as f64 / picks as f64;
+            assert!((share - weight / total).abs() < 0.02, "{:?}: {}", trick, share);
+        }
+    }
+
+    #[test]
+    fn picks_nothing_while_everything_is_on_cooldown() {
+        let mut rng = SimRng::from_seed(3);
+        let tricks = cruising_after(&[Trick::DiveDeep, Trick::BreachJump, Trick::Backflip, Trick::TailWalk]);
+        assert_eq!(pick_next(&tricks, Duration::from_secs(5), &mut rng), None);
+    }
+
+    #[test]
+    fn progress_runs_from_zero_to_one() {
+        let mut tricks = Tricks::default();
+        let start = Duration::from_secs(3);
+        tricks.start(Trick::BreachJump, start);
+        let duration = Duration::from_secs_f64(info(Trick::BreachJump).duration);
+
+        assert_eq!(tricks.progress(start), 0.0);
+        assert!((tricks.progress(start + duration / 2) - 0.5).abs() < 1e-9);
+        assert_eq!(tricks.progress(start + duration), 1.0);
+        // and stays there for tricks that run long
+        assert_eq!(tricks.progress(start + duration * 3), 1.0);
+    }
+}
@@ -1,3 +1,7 @@
+use bevy::math::Affine2;
+use bevy::prelude::{GlobalTransform, Image, Vec3, Vec4};
+use bevy::render::render_resource::TextureFormat;
+
 /// Kotlin-style `also` extension function.
 pub trait Also<C> {
     fn also(self, callback: C) -> Self;
@@ -13,3 +17,31 @@ where
         self
     }
 }
+
+/// Flattens a transform onto the XY plane. The
+/// Z scale is ignored since sprites here use 0.
+pub fn to_affine2(transform: &GlobalTransform) -> Affine2 {
+    let x_axis = (transform.rotation * Vec3::X).truncate();
+    Affine2::from_scale_angle_translation(
+        transform.scale.truncate(),
+        x_axis.y.atan2(x_axis.x),
+        transform.translation.truncate(),
+    )
+}
+
+/// Reads a texel as normalized RGBA, or transparent black
+/// if the image isn't in an 8-bit RGBA format.
+pub fn texel(image: &Image, x: u32, y: u32) -> Vec4 {
+    match image.texture_descriptor.format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => {
+            let i = ((y * image.texture_descriptor.size.width + x) * 4) as usize;
+            Vec4::new(
+                image.data[i] as f32,
+                image.data[i + 1] as f32,
+                image.data[i + 2] as f32,
+                image.data[i + 3] as f32,
+            ) / 255.0
+        }
+        _ => Vec4::ZERO,
+    }
+}
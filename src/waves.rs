@@ -0,0 +1,107 @@
+//! Height-field water surface, kept free of any Bevy
+//! types like [`physics`](crate::physics).
+
+use crate::physics::MAX_STEP_TIME;
+
+/// Horizontal distance between two columns of water.
+pub const COLUMN_WIDTH: f64 = 8.0;
+
+/// How fast ripples travel along the surface, in units per second.
+const WAVE_SPEED: f64 = 240.0;
+/// How strongly each column is pulled back to the rest height.
+const STIFFNESS: f64 = 1.0;
+/// How quickly ripples die down.
+const DAMPING: f64 = 0.6;
+/// Caps how hard a single splash can hit the surface.
+const MAX_SPLASH_VELOCITY: f64 = 600.0;
+/// How many columns on either side of a splash are disturbed.
+const SPLASH_RADIUS: usize = 3;
+
+/// A row of water columns driven by a damped 1D wave equation.
+///
+/// Column heights are offsets from the rest height of the water, and
+/// x coordinates run from `-width / 2` to `width / 2` like the tank.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WaveSurface {
+    width: f64,
+    heights: Vec<f64>,
+    velocities: Vec<f64>,
+}
+
+impl WaveSurface {
+    pub fn new(width: f64) -> Self {
+        let columns = (width / COLUMN_WIDTH).ceil() as usize + 1;
+        Self {
+            width,
+            heights: vec![0.0; columns],
+            velocities: vec![0.0; columns],
+        }
+    }
+
+    pub fn heights(&self) -> &[f64] {
+        &self.heights
+    }
+
+    /// X coordinate of the `i`th column.
+    pub fn column_x(&self, i: usize) -> f64 {
+        -self.width / 2.0 + i as f64 * COLUMN_WIDTH
+    }
+
+    /// Linearly interpolated surface height at `x`.
+    pub fn height_at(&self, x: f64) -> f64 {
+        let pos = ((x + self.width / 2.0) / COLUMN_WIDTH).clamp(0.0, (self.heights.len() - 1) as f64);
+        let i = (pos.floor() as usize).min(self.heights.len() - 1);
+        let j = (i + 1).min(self.heights.len() - 1);
+        let t = pos - i as f64;
+        self.heights[i] * (1.0 - t) + self.heights[j] * t
+    }
+
+    /// Pushes the surface around `x` with the given vertical velocity.
+    pub fn splash(&mut self, x: f64, velocity: f64) {
+        let velocity = velocity.clamp(-MAX_SPLASH_VELOCITY, MAX_SPLASH_VELOCITY);
+        let center = ((x + self.width / 2.0) / COLUMN_WIDTH).round();
+        if center < 0.0 || center >= self.heights.len() as f64 {
+            return;
+        }
+        let center = center as usize;
+
+        let start = center.saturating_sub(SPLASH_RADIUS);
+        let end = (center + SPLASH_RADIUS).min(self.heights.len() - 1);
+        for i in start..=end {
+            // cosine falloff so that the splash doesn't leave a hard edge
+            let dist = (i as f64 - center as f64).abs() / (SPLASH_RADIUS + 1) as f64;
+            let falloff = 0.5 + 0.5 * (dist * std::f64::consts::PI).cos();
+            self.velocities[i] += velocity * falloff;
+        }
+    }
+
+    /// Advances the surface by `dt` seconds, subdivided
+    /// into chunks of at most [`MAX_STEP_TIME`].
+    pub fn step(&mut self, dt: f64) {
+        let mut remaining = dt;
+        while remaining > 0.0 {
+            let delta = remaining.min(MAX_STEP_TIME);
+            self.substep(delta);
+            remaining -= delta;
+        }
+    }
+
+    fn substep(&mut self, delta: f64) {
+        let last = self.heights.len() - 1;
+        let tension = WAVE_SPEED * WAVE_SPEED / (COLUMN_WIDTH * COLUMN_WIDTH);
+
+        // Update velocities, with the edges reflecting waves back
+        for i in 0..=last {
+            let left = self.heights[i.saturating_sub(1)];
+            let right = self.heights[(i + 1).min(last)];
+            let laplacian = left + right - 2.0 * self.heights[i];
+            let accel = tension * laplacian - STIFFNESS * self.heights[i] - DAMPING * self.velocities[i];
+            self.velocities[i] += accel * delta;
+        }
+
+        // Update heights
+        for (height, velocity) in self.heights.iter_mut().zip(&self.velocities) {
+            *height += velocity * delta;
+        }
+    }
+}
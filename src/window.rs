@@ -1,18 +1,44 @@
-use bevy::prelude::{error, App, IntoExclusiveSystem, Plugin, ResMut};
-use bevy::window::WindowId;
+use bevy::core::Time;
+use bevy::math::Vec2;
+use bevy::prelude::{
+    error, info, App, CoreStage, EventReader, EventWriter, IntoExclusiveSystem, Local,
+    ParallelSystemDescriptorCoercion, Plugin, Res, ResMut,
+};
+use bevy::window::{CreateWindow, WindowCreated, WindowDescriptor, WindowId, WindowResized, WindowScaleFactorChanged};
 use bevy::winit::WinitWindows;
 use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::monitor::MonitorHandle;
 use winit::window::Window;
 
+use crate::cli::{Monitors, Options};
+use crate::tank::{Tank, Tanks};
+#[cfg(target_os = "linux")]
+use crate::x11::X11Window;
+
+/// Logical size of the first tank, where the dolphin lives.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WindowSize(pub Vec2);
+
 pub struct WindowHandlingPlugin;
 
 impl Plugin for WindowHandlingPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(setup.exclusive_system());
+        app.init_resource::<WindowSize>()
+            .init_resource::<Layout>()
+            .init_resource::<PendingWindows>()
+            .add_startup_system(setup.exclusive_system())
+            .add_system_to_stage(CoreStage::PreUpdate, setup_new_windows)
+            .add_system_to_stage(CoreStage::PreUpdate, relayout.label("relayout"))
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                update_window_size.label("window_size").after("relayout"),
+            );
 
-        #[cfg(target_os = "windows")]
-        if on_desktop() {
-            app.add_system(ensure_show_on_desktop.exclusive_system());
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
+        if let Some(options) = app.world.get_resource::<Options>() {
+            if options.desktop && !options.desktop_window {
+                app.add_system(ensure_show_on_desktop.exclusive_system());
+            }
         }
     }
 
@@ -21,44 +47,340 @@ impl Plugin for WindowHandlingPlugin {
     }
 }
 
-fn on_desktop() -> bool {
-    std::env::args().find(|x| x == "-d" || x == "-desktop").is_some()
+/// How often to check whether monitors or the work area changed, in seconds.
+const RELAYOUT_INTERVAL: f64 = 1.0;
+/// How often to check whether "show desktop" is active on X11, in seconds.
+#[cfg(target_os = "linux")]
+const SHOW_DESKTOP_INTERVAL: f64 = 0.25;
+
+/// Where a tank's window goes, in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Placement {
+    position: PhysicalPosition<i32>,
+    size: PhysicalSize<u32>,
+    scale_factor: f64,
 }
 
-pub fn setup(winit_windows: ResMut<WinitWindows>) {
+/// Where each tank's window was last put, in the same order as [`Tanks`].
+#[derive(Debug, Default)]
+struct Layout(Vec<Placement>);
+
+/// Windows for the other tanks, waiting for winit to create them.
+#[derive(Debug, Default)]
+struct PendingWindows(Vec<WindowId>);
+
+fn setup(
+    winit_windows: ResMut<WinitWindows>,
+    options: Res<Options>,
+    descriptor: Res<WindowDescriptor>,
+    mut tanks: ResMut<Tanks>,
+    mut layout: ResMut<Layout>,
+    mut pending_windows: ResMut<PendingWindows>,
+    mut create_window: EventWriter<CreateWindow>,
+) {
     let primary = winit_windows
         .get_window(WindowId::primary())
         .expect("Primary window doesn't exist?");
-    let monitor = primary.current_monitor().expect("Current window has no monitor?");
+    if let Monitors::Index(index) = options.monitors {
+        if primary.available_monitors().nth(index).is_none() {
+            error!("There's no monitor {}, falling back to the primary monitor", index);
+        }
+    }
+
+    layout.0 = placements(primary, &options);
+    tanks.tanks.clear();
+    tanks.span = options.monitors == Monitors::Span;
+    for (index, placement) in layout.0.iter().enumerate() {
+        let window = if index == 0 {
+            place_window(primary, placement, &options);
+            WindowId::primary()
+        } else {
+            let id = WindowId::new();
+            create_window.send(CreateWindow {
+                id,
+                descriptor: descriptor.clone(),
+            });
+            pending_windows.0.push(id);
+            id
+        };
+        tanks.tanks.push(Tank {
+            window,
+            center: Vec2::ZERO,
+            size: Vec2::ZERO,
+        });
+    }
+    apply_layout(&mut tanks, &layout);
+}
+
+/// Where every selected monitor's tank should go. The first one is always
+/// on the primary monitor when it's selected, since the dolphin lives there.
+fn placements(window: &Window, options: &Options) -> Vec<Placement> {
+    let primary_monitor = window.primary_monitor();
+    let mut monitors = match options.monitors {
+        Monitors::Primary => primary_monitor.into_iter().collect(),
+        Monitors::All | Monitors::Span => {
+            let mut monitors = window.available_monitors().collect::<Vec<_>>();
+            monitors.sort_by_key(|monitor| Some(monitor) != primary_monitor.as_ref());
+            monitors
+        }
+        Monitors::Index(index) => window
+            .available_monitors()
+            .nth(index)
+            .or(primary_monitor)
+            .into_iter()
+            .collect::<Vec<_>>(),
+    };
+    if monitors.is_empty() {
+        monitors.push(window.current_monitor().expect("Current window has no monitor?"));
+    }
+
+    monitors
+        .iter()
+        .map(|monitor| {
+            let (mut position, mut size) = (monitor.position(), monitor.size());
+            if options.work_area {
+                if let Some((work_position, work_size)) = work_area(window, monitor)
+                    .and_then(|(work_position, work_size)| intersect(position, size, work_position, work_size))
+                {
+                    position = work_position;
+                    size = work_size;
+                }
+            }
+            Placement {
+                position,
+                size,
+                scale_factor: monitor.scale_factor(),
+            }
+        })
+        .collect()
+}
 
+/// Overlap of two rectangles, if there is any.
+fn intersect(
+    a_position: PhysicalPosition<i32>,
+    a_size: PhysicalSize<u32>,
+    b_position: PhysicalPosition<i32>,
+    b_size: PhysicalSize<u32>,
+) -> Option<(PhysicalPosition<i32>, PhysicalSize<u32>)> {
+    let left = a_position.x.max(b_position.x);
+    let top = a_position.y.max(b_position.y);
+    let right = (a_position.x + a_size.width as i32).min(b_position.x + b_size.width as i32);
+    let bottom = (a_position.y + a_size.height as i32).min(b_position.y + b_size.height as i32);
+    (right > left && bottom > top).then(|| {
+        (
+            PhysicalPosition::new(left, top),
+            PhysicalSize::new((right - left) as u32, (bottom - top) as u32),
+        )
+    })
+}
+
+/// Lays out the tanks like their windows, but with y going
+/// up and the first tank centered on the origin.
+fn apply_layout(tanks: &mut Tanks, layout: &Layout) {
+    let mut origin = None;
+    for (tank, placement) in tanks.tanks.iter_mut().zip(layout.0.iter()) {
+        let position = placement.position.to_logical::<f32>(placement.scale_factor);
+        let size = window_size(placement.size).to_logical::<f32>(placement.scale_factor);
+        let size = Vec2::new(size.width, size.height);
+        let center = Vec2::new(position.x, position.y) + size / 2.0;
+        let offset = center - *origin.get_or_insert(center);
+
+        tank.center = Vec2::new(offset.x, -offset.y);
+        tank.size = size;
+    }
+}
+
+/// Moves the tanks when monitors are plugged in, change resolution,
+/// or (with `--work-area`) when panels come and go.
+fn relayout(
+    winit_windows: Res<WinitWindows>,
+    options: Res<Options>,
+    time: Res<Time>,
+    pending_windows: Res<PendingWindows>,
+    mut last_check: Local<f64>,
+    mut layout: ResMut<Layout>,
+    mut tanks: ResMut<Tanks>,
+) {
+    let now = time.seconds_since_startup();
+    if now - *last_check < RELAYOUT_INTERVAL {
+        return;
+    }
+    *last_check = now;
+
+    let primary = winit_windows.get_window(WindowId::primary()).unwrap();
+    let mut new_placements = placements(primary, &options);
+    // tanks can't come and go, so unplugged monitors' tanks stay where they were
+    new_placements.truncate(layout.0.len());
+    new_placements.extend_from_slice(&layout.0[new_placements.len()..]);
+    if new_placements == layout.0 {
+        return;
+    }
+
+    for (index, (old, new)) in layout.0.iter().zip(new_placements.iter()).enumerate() {
+        let window = tanks.tanks[index].window;
+        if old == new || pending_windows.0.contains(&window) {
+            continue;
+        }
+        info!("Moving tank {} to {:?}", index, new);
+        if let Some(window) = winit_windows.get_window(window) {
+            place_window(window, new, &options);
+        }
+    }
+    layout.0 = new_placements;
+    apply_layout(&mut tanks, &layout);
+}
+
+/// Winit creates windows between frames, so the other
+/// tanks' windows can only be set up once they exist.
+fn setup_new_windows(
+    mut created: EventReader<WindowCreated>,
+    winit_windows: Res<WinitWindows>,
+    mut pending_windows: ResMut<PendingWindows>,
+    tanks: Res<Tanks>,
+    layout: Res<Layout>,
+    options: Res<Options>,
+) {
+    for event in created.iter() {
+        if let Some(index) = pending_windows.0.iter().position(|id| *id == event.id) {
+            pending_windows.0.remove(index);
+            let placement = tanks
+                .tanks
+                .iter()
+                .position(|tank| tank.window == event.id)
+                .and_then(|tank| layout.0.get(tank));
+            if let (Some(window), Some(placement)) = (winit_windows.get_window(event.id), placement) {
+                place_window(window, placement, &options);
+            }
+        }
+    }
+}
+
+/// Covers a monitor (or its work area) with `window`.
+fn place_window(window: &Window, placement: &Placement, options: &Options) {
     // Display only on the desktop rather than over all other apps
-    // if `-desktop` is passed
-    let on_desktop = on_desktop();
+    // if `--desktop` is passed
+    let on_desktop = options.on_desktop();
     if !on_desktop {
-        primary.set_always_on_top(true);
+        window.set_always_on_top(true);
     }
 
     // on Windows, making the window take up the full screen
     // seems to automatically put it into fullscreen mode,
     // which we don't want
-    primary.set_outer_position({
-        let pos = monitor.position();
-        PhysicalPosition::new(pos.x, pos.y + 1)
-    });
-    primary.set_inner_size({
-        let size = monitor.size();
-        PhysicalSize::new(size.width, size.height - 1)
-    });
+    window.set_outer_position(PhysicalPosition::new(placement.position.x, placement.position.y + 1));
+    window.set_inner_size(window_size(placement.size));
 
     #[cfg(target_os = "windows")]
     unsafe {
-        setup_win32(&primary);
+        setup_win32(window, options.opacity);
+    }
+
+    #[cfg(target_os = "linux")]
+    setup_x11(window, options);
+}
+
+fn window_size(monitor_size: PhysicalSize<u32>) -> PhysicalSize<u32> {
+    PhysicalSize::new(monitor_size.width, monitor_size.height.saturating_sub(1))
+}
+
+/// The part of the desktop that isn't covered by panels
+/// and taskbars, in physical pixels.
+#[cfg(target_os = "windows")]
+fn work_area(_window: &Window, monitor: &MonitorHandle) -> Option<(PhysicalPosition<i32>, PhysicalSize<u32>)> {
+    use winapi::shared::minwindef::DWORD;
+    use winapi::shared::windef::{HMONITOR, RECT};
+    use winapi::um::winuser::*;
+    use winit::platform::windows::MonitorHandleExtWindows;
+
+    let mut rect: RECT = unsafe { std::mem::zeroed() };
+    let position = monitor.position();
+    unsafe {
+        // the primary monitor is always at the origin, and
+        // it's the only monitor SPI_GETWORKAREA knows about
+        if position.x == 0 && position.y == 0 {
+            if SystemParametersInfoW(SPI_GETWORKAREA, 0, &mut rect as *mut RECT as *mut _, 0) == 0 {
+                return None;
+            }
+        } else {
+            let mut monitor_info: MONITORINFO = std::mem::zeroed();
+            monitor_info.cbSize = std::mem::size_of::<MONITORINFO>() as DWORD;
+            if GetMonitorInfoW(monitor.hmonitor() as HMONITOR, &mut monitor_info) == 0 {
+                return None;
+            }
+            rect = monitor_info.rcWork;
+        }
+    }
+    Some((
+        PhysicalPosition::new(rect.left, rect.top),
+        PhysicalSize::new((rect.right - rect.left) as u32, (rect.bottom - rect.top) as u32),
+    ))
+}
+
+/// EWMH only has a single work area spanning every monitor,
+/// which gets cut down to each monitor by the caller.
+#[cfg(target_os = "linux")]
+fn work_area(window: &Window, _monitor: &MonitorHandle) -> Option<(PhysicalPosition<i32>, PhysicalSize<u32>)> {
+    let x11_window = X11Window::new(window)?;
+    let desktop = x11_window.root_cardinal("_NET_CURRENT_DESKTOP").unwrap_or(0) as usize;
+    // four cardinals (x, y, width, height) for every virtual desktop
+    let work_areas = x11_window.root_cardinals("_NET_WORKAREA");
+    let area = work_areas
+        .chunks_exact(4)
+        .nth(desktop)
+        .or_else(|| work_areas.chunks_exact(4).next())?;
+    Some((
+        PhysicalPosition::new(area[0] as i32, area[1] as i32),
+        PhysicalSize::new(area[2] as u32, area[3] as u32),
+    ))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+fn work_area(_window: &Window, _monitor: &MonitorHandle) -> Option<(PhysicalPosition<i32>, PhysicalSize<u32>)> {
+    None
+}
+
+/// Hides the window without quitting, or brings it back.
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+pub fn set_visible(window: &Window, options: &Options, visible: bool) {
+    window.set_visible(visible);
+
+    // window managers forget about a window's state once it's hidden
+    #[cfg(target_os = "linux")]
+    if visible {
+        setup_x11(window, options);
+    }
+}
+
+fn update_window_size(
+    mut resized: EventReader<WindowResized>,
+    mut scale_factor_changed: EventReader<WindowScaleFactorChanged>,
+    winit_windows: Res<WinitWindows>,
+    mut tanks: ResMut<Tanks>,
+    mut window_size: ResMut<WindowSize>,
+) {
+    let changed = resized
+        .iter()
+        .map(|event| event.id)
+        .chain(scale_factor_changed.iter().map(|event| event.id))
+        .collect::<Vec<_>>();
+    if !changed.is_empty() {
+        for tank in tanks.tanks.iter_mut().filter(|tank| changed.contains(&tank.window)) {
+            if let Some(window) = winit_windows.get_window(tank.window) {
+                let logical_size = window.inner_size().to_logical::<f32>(window.scale_factor());
+                // minimized windows shrink down to nothing, but
+                // there's no point in fitting the tank to that
+                if logical_size.width >= 1.0 && logical_size.height >= 1.0 {
+                    tank.size = Vec2::new(logical_size.width, logical_size.height);
+                }
+            }
+        }
     }
+    window_size.0 = tanks.tanks[0].size;
 }
 
 /// Removes the window from the taskbar and passes through clicks.
 #[cfg(target_os = "windows")]
-unsafe fn setup_win32(window: &Window) {
+unsafe fn setup_win32(window: &Window, opacity: f32) {
     use std::ffi::c_void;
 
     use raw_window_handle::{HasRawWindowHandle, RawWindowHandle, Win32Handle};
@@ -78,7 +400,7 @@ unsafe fn setup_win32(window: &Window) {
         ex_style &= !WS_EX_APPWINDOW as LONG_PTR;
         ex_style &= !WS_EX_ACCEPTFILES as LONG_PTR;
         SetWindowLongPtrW(hwnd, GWL_EXSTYLE, ex_style);
-        SetLayeredWindowAttributes(hwnd, 0, 255, LWA_ALPHA);
+        SetLayeredWindowAttributes(hwnd, 0, (opacity * 255.0).round() as u8, LWA_ALPHA);
 
         const YES: BOOL = true as BOOL;
         DwmSetWindowAttribute(
@@ -95,8 +417,212 @@ unsafe fn setup_win32(window: &Window) {
     }
 }
 
+/// Removes the window from the taskbar and pager and passes through clicks.
+#[cfg(target_os = "linux")]
+fn setup_x11(window: &Window, options: &Options) {
+    use std::os::raw::c_long;
+
+    let x11_window = match X11Window::new(window) {
+        Some(x11_window) => x11_window,
+        None => {
+            error!("Couldn't get X11 window, things will probably look weird!");
+            return;
+        }
+    };
+
+    // Window managers only read a window's type when it's mapped, so
+    // take the window down while it's set up and put it back afterwards
+    x11_window.withdraw();
+
+    // Docks aren't decorated, focused, or given shadows by window
+    // managers and compositors, but ask compositors to keep
+    // compositing us anyway so that transparency still works
+    x11_window.set_cardinals("_NET_WM_BYPASS_COMPOSITOR", &[2]);
+
+    // `--desktop-window` makes the overlay act as the desktop window itself,
+    // which the window manager keeps below everything else and shows with the desktop
+    let window_type = if options.desktop_window {
+        "_NET_WM_WINDOW_TYPE_DESKTOP"
+    } else {
+        "_NET_WM_WINDOW_TYPE_DOCK"
+    };
+    x11_window.set_atoms("_NET_WM_WINDOW_TYPE", &[x11_window.atom(window_type)]);
+
+    // while it's withdrawn, the state can be set directly too
+    let layer = if options.on_desktop() {
+        "_NET_WM_STATE_BELOW"
+    } else {
+        "_NET_WM_STATE_ABOVE"
+    };
+    x11_window.set_atoms(
+        "_NET_WM_STATE",
+        &["_NET_WM_STATE_SKIP_TASKBAR", "_NET_WM_STATE_SKIP_PAGER", layer].map(|state| x11_window.atom(state)),
+    );
+
+    if options.opacity < 1.0 {
+        let opacity = (options.opacity as f64 * u32::MAX as f64).round() as c_long;
+        x11_window.set_cardinals("_NET_WM_WINDOW_OPACITY", &[opacity]);
+    }
+
+    if !x11_window.set_click_through(true) {
+        error!("XFixes isn't available, so clicks won't pass through the window!");
+    }
+    x11_window.map();
+    x11_window.flush();
+}
+
+/// Cursor position in logical pixels from the top left of the window.
+/// Unlike winit's cursor events, this still works while the window
+/// is letting clicks pass through.
+#[cfg(target_os = "windows")]
+pub fn cursor_position(window: &Window) -> Option<Vec2> {
+    use raw_window_handle::{HasRawWindowHandle, RawWindowHandle, Win32Handle};
+    use winapi::shared::windef::{HWND, POINT};
+    use winapi::um::winuser::*;
+
+    if let RawWindowHandle::Win32(Win32Handle { hwnd, .. }) = window.raw_window_handle() {
+        let mut point = POINT { x: 0, y: 0 };
+        unsafe {
+            if GetCursorPos(&mut point) == 0 || ScreenToClient(hwnd as HWND, &mut point) == 0 {
+                return None;
+            }
+        }
+        Some(Vec2::new(point.x as f32, point.y as f32) / window.scale_factor() as f32)
+    } else {
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn cursor_position(window: &Window) -> Option<Vec2> {
+    let (x, y) = X11Window::new(window)?.cursor_position()?;
+    Some(Vec2::new(x as f32, y as f32) / window.scale_factor() as f32)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+pub fn cursor_position(_window: &Window) -> Option<Vec2> {
+    None
+}
+
+/// Toggles whether clicks pass through the window
+/// to whatever is behind it.
 #[cfg(target_os = "windows")]
-fn ensure_show_on_desktop(winit_windows: ResMut<WinitWindows>) {
+pub fn set_click_through(window: &Window, click_through: bool) {
+    use raw_window_handle::{HasRawWindowHandle, RawWindowHandle, Win32Handle};
+    use winapi::shared::basetsd::LONG_PTR;
+    use winapi::shared::windef::HWND;
+    use winapi::um::winuser::*;
+
+    if let RawWindowHandle::Win32(Win32Handle { hwnd, .. }) = window.raw_window_handle() {
+        let hwnd = hwnd as HWND;
+        unsafe {
+            let mut ex_style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+            if click_through {
+                ex_style |= WS_EX_TRANSPARENT as LONG_PTR;
+            } else {
+                ex_style &= !WS_EX_TRANSPARENT as LONG_PTR;
+            }
+            SetWindowLongPtrW(hwnd, GWL_EXSTYLE, ex_style);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn set_click_through(window: &Window, click_through: bool) {
+    if let Some(x11_window) = X11Window::new(window) {
+        x11_window.set_click_through(click_through);
+        x11_window.flush();
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+pub fn set_click_through(_window: &Window, _click_through: bool) {}
+
+/// Whether the focused window is some other app running
+/// fullscreen on the same monitor as the overlay.
+#[cfg(target_os = "windows")]
+pub fn fullscreen_app_focused(window: &Window) -> bool {
+    use std::ffi::CString;
+
+    use raw_window_handle::{HasRawWindowHandle, RawWindowHandle, Win32Handle};
+    use winapi::shared::minwindef::DWORD;
+    use winapi::shared::windef::{HWND, RECT};
+    use winapi::um::winuser::*;
+
+    let hwnd = match window.raw_window_handle() {
+        RawWindowHandle::Win32(Win32Handle { hwnd, .. }) => hwnd as HWND,
+        _ => return false,
+    };
+    unsafe {
+        let foreground = GetForegroundWindow();
+        if foreground.is_null() || foreground == hwnd {
+            return false;
+        }
+        // the desktop covers the whole monitor too
+        let def_view_class = CString::new("SHELLDLL_DefView").unwrap();
+        if foreground == GetShellWindow()
+            || !FindWindowExA(
+                foreground,
+                std::ptr::null_mut(),
+                def_view_class.as_ptr(),
+                std::ptr::null_mut(),
+            )
+            .is_null()
+        {
+            return false;
+        }
+
+        let monitor = MonitorFromWindow(foreground, MONITOR_DEFAULTTONULL);
+        if monitor.is_null() || monitor != MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST) {
+            return false;
+        }
+        let mut monitor_info: MONITORINFO = std::mem::zeroed();
+        monitor_info.cbSize = std::mem::size_of::<MONITORINFO>() as DWORD;
+        let mut rect: RECT = std::mem::zeroed();
+        if GetMonitorInfoW(monitor, &mut monitor_info) == 0 || GetWindowRect(foreground, &mut rect) == 0 {
+            return false;
+        }
+        let bounds = monitor_info.rcMonitor;
+        rect.left <= bounds.left && rect.top <= bounds.top && rect.right >= bounds.right && rect.bottom >= bounds.bottom
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn fullscreen_app_focused(window: &Window) -> bool {
+    let x11_window = match X11Window::new(window) {
+        Some(x11_window) => x11_window,
+        None => return false,
+    };
+    let active = match x11_window.active_window() {
+        Some(active) if active != x11_window.window => active,
+        _ => return false,
+    };
+    if !x11_window
+        .wm_state_of(active)
+        .contains(&x11_window.atom("_NET_WM_STATE_FULLSCREEN"))
+    {
+        return false;
+    }
+
+    // fullscreen windows on other monitors don't cover us
+    match (x11_window.geometry_of(active), window.current_monitor()) {
+        (Some((x, y, width, height)), Some(monitor)) => {
+            let (center_x, center_y) = (x + width / 2, y + height / 2);
+            let (position, size) = (monitor.position(), monitor.size());
+            (position.x..position.x + size.width as i32).contains(&center_x)
+                && (position.y..position.y + size.height as i32).contains(&center_y)
+        }
+        _ => true,
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+pub fn fullscreen_app_focused(_window: &Window) -> bool {
+    false
+}
+
+#[cfg(target_os = "windows")]
+fn ensure_show_on_desktop(winit_windows: ResMut<WinitWindows>, mut showing_desktop: Local<bool>) {
     use std::ffi::CString;
 
     use raw_window_handle::{HasRawWindowHandle, RawWindowHandle, Win32Handle};
@@ -173,20 +699,69 @@ fn ensure_show_on_desktop(winit_windows: ResMut<WinitWindows>) {
             lparam.state == EnumWindowCallbackState::FoundDolphin
         };
 
-        if show_desktop {
-            println!("Show Desktop detected!");
+        if show_desktop && !*showing_desktop {
+            info!("Show Desktop detected");
         }
-        unsafe {
-            // set always-on-top if show desktop is detected
-            SetWindowPos(
-                primary_hwnd,
-                if show_desktop { HWND_TOPMOST } else { HWND_NOTOPMOST },
-                0,
-                0,
-                0,
-                0,
-                SWP_ASYNCWINDOWPOS | SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
-            );
+        *showing_desktop = show_desktop;
+        // the other tanks' windows are stacked the same way as the primary one
+        for window in winit_windows.windows.values() {
+            if let RawWindowHandle::Win32(Win32Handle { hwnd, .. }) = window.raw_window_handle() {
+                unsafe {
+                    // set always-on-top if show desktop is detected
+                    SetWindowPos(
+                        hwnd as HWND,
+                        if show_desktop { HWND_TOPMOST } else { HWND_NOTOPMOST },
+                        0,
+                        0,
+                        0,
+                        0,
+                        SWP_ASYNCWINDOWPOS | SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn ensure_show_on_desktop(
+    winit_windows: ResMut<WinitWindows>,
+    time: Res<Time>,
+    mut last_check: Local<f64>,
+    mut showing_desktop: Local<bool>,
+) {
+    // asking the X server is a round trip, so don't do it every frame
+    let now = time.seconds_since_startup();
+    if now - *last_check < SHOW_DESKTOP_INTERVAL {
+        return;
+    }
+    *last_check = now;
+
+    let primary = winit_windows.get_window(WindowId::primary()).unwrap();
+    // EWMH window managers set this on the root window while "show desktop"
+    // is active, hiding every normal window. Move ourselves out of the
+    // "below" layer so that we still show up over the desktop window.
+    let show_desktop = match X11Window::new(primary) {
+        Some(x11_window) => x11_window.root_cardinal("_NET_SHOWING_DESKTOP").unwrap_or(0) != 0,
+        None => return,
+    };
+    if show_desktop == *showing_desktop {
+        return;
+    }
+    *showing_desktop = show_desktop;
+
+    if show_desktop {
+        info!("Show Desktop detected");
+    }
+    for x11_window in winit_windows.windows.values().filter_map(X11Window::new) {
+        if show_desktop {
+            x11_window.set_wm_state(false, "_NET_WM_STATE_BELOW", None);
+            x11_window.set_wm_state(true, "_NET_WM_STATE_ABOVE", None);
+            x11_window.raise();
+        } else {
+            x11_window.set_wm_state(false, "_NET_WM_STATE_ABOVE", None);
+            x11_window.set_wm_state(true, "_NET_WM_STATE_BELOW", None);
         }
+        x11_window.flush();
     }
 }
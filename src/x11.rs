@@ -0,0 +1,275 @@
+//! Thin wrapper over winit's Xlib connection for the
+//! handful of EWMH hints and extensions the overlay uses.
+
+use std::ffi::CString;
+use std::os::raw::{c_int, c_long, c_uchar, c_uint, c_ulong};
+use std::sync::Arc;
+
+use lazy_static::lazy_static;
+use winit::platform::unix::x11::XConnection;
+use winit::platform::unix::WindowExtUnix;
+use winit::window::Window;
+use x11_dl::xfixes::Xlib_xfixes;
+use x11_dl::xlib::{self, Atom};
+
+/// `ShapeInput` from the XShape extension, which x11-dl doesn't define.
+const SHAPE_INPUT: c_int = 2;
+
+/// `_NET_WM_STATE` client message actions.
+const NET_WM_STATE_REMOVE: c_long = 0;
+const NET_WM_STATE_ADD: c_long = 1;
+/// Marks requests as coming from a normal application
+/// rather than a pager or taskbar.
+const SOURCE_APPLICATION: c_long = 1;
+/// More than enough for any list property the overlay reads.
+const MAX_PROPERTY_ITEMS: c_long = 1024;
+
+lazy_static! {
+    static ref XFIXES: Option<Xlib_xfixes> = Xlib_xfixes::open().ok();
+}
+
+pub struct X11Window {
+    xconn: Arc<XConnection>,
+    pub window: c_ulong,
+    pub root: c_ulong,
+}
+
+impl X11Window {
+    /// Returns `None` if the window isn't using X11 (on Wayland for example).
+    pub fn new(window: &Window) -> Option<Self> {
+        let xconn = window.xlib_xconnection()?;
+        let xwindow = window.xlib_window()?;
+        let root = unsafe { (xconn.xlib.XDefaultRootWindow)(xconn.display) };
+        Some(Self {
+            xconn,
+            window: xwindow,
+            root,
+        })
+    }
+
+    pub fn atom(&self, name: &str) -> Atom {
+        let name = CString::new(name).unwrap();
+        unsafe { (self.xconn.xlib.XInternAtom)(self.xconn.display, name.as_ptr(), xlib::False) }
+    }
+
+    /// Replaces an `ATOM[]` property on this window.
+    pub fn set_atoms(&self, property: &str, values: &[Atom]) {
+        self.change_property(property, xlib::XA_ATOM, values);
+    }
+
+    /// Replaces a `CARDINAL[]` property on this window.
+    pub fn set_cardinals(&self, property: &str, values: &[c_long]) {
+        self.change_property(property, xlib::XA_CARDINAL, values);
+    }
+
+    fn change_property<T>(&self, property: &str, property_type: Atom, values: &[T]) {
+        // format 32 properties are passed to Xlib as arrays of longs
+        assert_eq!(std::mem::size_of::<T>(), std::mem::size_of::<c_long>());
+        unsafe {
+            (self.xconn.xlib.XChangeProperty)(
+                self.xconn.display,
+                self.window,
+                self.atom(property),
+                property_type,
+                32,
+                xlib::PropModeReplace,
+                values.as_ptr() as *const c_uchar,
+                values.len() as c_int,
+            );
+        }
+    }
+
+    /// Reads the first item of a `CARDINAL` property on the root window.
+    pub fn root_cardinal(&self, property: &str) -> Option<c_long> {
+        self.get_property(self.root, property, xlib::XA_CARDINAL, 1)
+            .first()
+            .copied()
+    }
+
+    /// Reads every item of a `CARDINAL[]` property on the root window.
+    pub fn root_cardinals(&self, property: &str) -> Vec<c_long> {
+        self.get_property(self.root, property, xlib::XA_CARDINAL, MAX_PROPERTY_ITEMS)
+    }
+
+    /// The window the window manager says has focus, if any.
+    pub fn active_window(&self) -> Option<c_ulong> {
+        self.get_property(self.root, "_NET_ACTIVE_WINDOW", xlib::XA_WINDOW, 1)
+            .first()
+            .map(|window| *window as c_ulong)
+            .filter(|window| *window != 0)
+    }
+
+    /// The `_NET_WM_STATE`s some other window currently has.
+    pub fn wm_state_of(&self, window: c_ulong) -> Vec<Atom> {
+        self.get_property(window, "_NET_WM_STATE", xlib::XA_ATOM, MAX_PROPERTY_ITEMS)
+            .into_iter()
+            .map(|atom| atom as Atom)
+            .collect()
+    }
+
+    /// Position and size of some other window relative to the root window,
+    /// in physical pixels, as `(x, y, width, height)`.
+    pub fn geometry_of(&self, window: c_ulong) -> Option<(i32, i32, i32, i32)> {
+        let mut attributes = std::mem::MaybeUninit::<xlib::XWindowAttributes>::uninit();
+        let (mut x, mut y, mut child) = (0, 0, 0);
+        unsafe {
+            if (self.xconn.xlib.XGetWindowAttributes)(self.xconn.display, window, attributes.as_mut_ptr()) == 0 {
+                return None;
+            }
+            let attributes = attributes.assume_init();
+            // attributes are relative to the parent, which is usually a frame
+            if (self.xconn.xlib.XTranslateCoordinates)(
+                self.xconn.display,
+                window,
+                self.root,
+                0,
+                0,
+                &mut x,
+                &mut y,
+                &mut child,
+            ) == xlib::False
+            {
+                return None;
+            }
+            Some((x, y, attributes.width, attributes.height))
+        }
+    }
+
+    /// Reads up to `max_items` of a format 32 property.
+    fn get_property(&self, window: c_ulong, property: &str, property_type: Atom, max_items: c_long) -> Vec<c_long> {
+        let mut actual_type = 0;
+        let mut actual_format = 0;
+        let (mut items, mut bytes_after) = (0, 0);
+        let mut data: *mut c_uchar = std::ptr::null_mut();
+        let status = unsafe {
+            (self.xconn.xlib.XGetWindowProperty)(
+                self.xconn.display,
+                window,
+                self.atom(property),
+                0,
+                max_items,
+                xlib::False,
+                property_type,
+                &mut actual_type,
+                &mut actual_format,
+                &mut items,
+                &mut bytes_after,
+                &mut data,
+            )
+        };
+        if data.is_null() {
+            return vec![];
+        }
+        // format 32 properties come back as arrays of longs too
+        let values = if status == xlib::Success as c_int && actual_format == 32 {
+            unsafe { std::slice::from_raw_parts(data as *const c_long, items as usize) }.to_vec()
+        } else {
+            vec![]
+        };
+        unsafe {
+            (self.xconn.xlib.XFree)(data as *mut _);
+        }
+        values
+    }
+
+    /// Unmaps the window and tells the window manager it's gone, so that
+    /// properties it only reads on mapping can be changed. See [`map`](Self::map).
+    pub fn withdraw(&self) {
+        unsafe {
+            let screen = (self.xconn.xlib.XDefaultScreen)(self.xconn.display);
+            (self.xconn.xlib.XWithdrawWindow)(self.xconn.display, self.window, screen);
+        }
+    }
+
+    pub fn map(&self) {
+        unsafe {
+            (self.xconn.xlib.XMapWindow)(self.xconn.display, self.window);
+        }
+    }
+
+    pub fn raise(&self) {
+        unsafe {
+            (self.xconn.xlib.XRaiseWindow)(self.xconn.display, self.window);
+        }
+    }
+
+    /// Asks the window manager to add or remove up to two `_NET_WM_STATE`s.
+    /// Setting the property directly only works before the window is mapped.
+    pub fn set_wm_state(&self, add: bool, first: &str, second: Option<&str>) {
+        let action = if add { NET_WM_STATE_ADD } else { NET_WM_STATE_REMOVE };
+        let first = self.atom(first) as c_long;
+        let second = second.map_or(0, |state| self.atom(state) as c_long);
+        self.send_client_message("_NET_WM_STATE", [action, first, second, SOURCE_APPLICATION, 0]);
+    }
+
+    fn send_client_message(&self, message_type: &str, data: [c_long; 5]) {
+        let event = xlib::XClientMessageEvent {
+            type_: xlib::ClientMessage,
+            serial: 0,
+            send_event: xlib::True,
+            display: self.xconn.display,
+            window: self.window,
+            message_type: self.atom(message_type),
+            format: 32,
+            data: xlib::ClientMessageData::from(data),
+        };
+        unsafe {
+            (self.xconn.xlib.XSendEvent)(
+                self.xconn.display,
+                self.root,
+                xlib::False,
+                xlib::SubstructureRedirectMask | xlib::SubstructureNotifyMask,
+                &mut xlib::XEvent::from(event),
+            );
+        }
+    }
+
+    /// Sets an empty XShape input region so that clicks pass straight
+    /// through the window, or resets it to cover the whole window.
+    /// Returns `false` if the XFixes extension isn't available.
+    pub fn set_click_through(&self, click_through: bool) -> bool {
+        let xfixes = match XFIXES.as_ref() {
+            Some(xfixes) => xfixes,
+            None => return false,
+        };
+        unsafe {
+            if click_through {
+                let region = (xfixes.XFixesCreateRegion)(self.xconn.display, std::ptr::null_mut(), 0);
+                (xfixes.XFixesSetWindowShapeRegion)(self.xconn.display, self.window, SHAPE_INPUT, 0, 0, region);
+                (xfixes.XFixesDestroyRegion)(self.xconn.display, region);
+            } else {
+                // a region of `None` resets the input shape to the default
+                (xfixes.XFixesSetWindowShapeRegion)(self.xconn.display, self.window, SHAPE_INPUT, 0, 0, 0);
+            }
+        }
+        true
+    }
+
+    /// Cursor position in physical pixels from the top left of the window,
+    /// queried directly so that it works even while clicks pass through.
+    pub fn cursor_position(&self) -> Option<(i32, i32)> {
+        let (mut root, mut child) = (0, 0);
+        let (mut root_x, mut root_y, mut x, mut y) = (0, 0, 0, 0);
+        let mut mask: c_uint = 0;
+        let same_screen = unsafe {
+            (self.xconn.xlib.XQueryPointer)(
+                self.xconn.display,
+                self.window,
+                &mut root,
+                &mut child,
+                &mut root_x,
+                &mut root_y,
+                &mut x,
+                &mut y,
+                &mut mask,
+            )
+        };
+        (same_screen != xlib::False).then(|| (x, y))
+    }
+
+    pub fn flush(&self) {
+        unsafe {
+            (self.xconn.xlib.XFlush)(self.xconn.display);
+        }
+    }
+}